@@ -0,0 +1,132 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use esolang::{
+    machine::Machine,
+    memory::{CachedMemory, FileMemory, InMemoryMemory, Memory},
+    types::{Instruction, Offset, OffsetPair, Register},
+};
+
+const ITERATIONS: u64 = 1000;
+
+/// A loop that counts register 0 down to zero, doing some register
+/// arithmetic on each pass. It modifies itself: the counter is stored into
+/// the comparison, and the comparison's result into the `JumpIf`, so those
+/// two are fetched again on every pass.
+fn self_modifying_loop() -> Vec<u8> {
+    InMemoryMemory::builder()
+        .instruction(Instruction::SetRegister, (Register(0), ITERATIONS))
+        .instruction(Instruction::SetRegister, (Register(1), 1_u64))
+        .instruction(
+            Instruction::SubtractRegister,
+            (Register(0), Register(0), Register(1)),
+        )
+        .instruction(
+            Instruction::AddRegister,
+            (Register(2), Register(2), Register(0)),
+        )
+        .instruction(
+            Instruction::MultiplyRegister,
+            (Register(3), Register(2), Register(1)),
+        )
+        .instruction(
+            Instruction::AddRegister,
+            (Register(4), Register(4), Register(3)),
+        )
+        .instruction(
+            Instruction::StoreRegister,
+            (Register(0), OffsetPair(Offset(2), Offset(-10))),
+        )
+        .instruction(
+            Instruction::NotEqualInteger64,
+            (0_u64, 0_u64, OffsetPair(Offset(1), Offset(-2))),
+        )
+        .instruction(Instruction::JumpIf, (false, Offset(-53)))
+        .build()
+        .memory
+}
+
+/// The same loop, but branching on the counter register directly, so it
+/// never writes to memory and every pass after the first is a cache hit.
+fn register_loop() -> Vec<u8> {
+    InMemoryMemory::builder()
+        .instruction(Instruction::SetRegister, (Register(0), ITERATIONS))
+        .instruction(Instruction::SetRegister, (Register(1), 1_u64))
+        .instruction(
+            Instruction::SubtractRegister,
+            (Register(0), Register(0), Register(1)),
+        )
+        .instruction(
+            Instruction::AddRegister,
+            (Register(2), Register(2), Register(0)),
+        )
+        .instruction(
+            Instruction::MultiplyRegister,
+            (Register(3), Register(2), Register(1)),
+        )
+        .instruction(
+            Instruction::AddRegister,
+            (Register(4), Register(4), Register(3)),
+        )
+        .instruction(Instruction::JumpIfRegister, (Register(0), Offset(-25)))
+        .build()
+        .memory
+}
+
+type Program = fn() -> Vec<u8>;
+
+const PROGRAMS: [(&str, Program); 2] = [
+    ("self_modifying", self_modifying_loop),
+    ("registers", register_loop),
+];
+
+fn run(memory: impl Memory<Error: std::fmt::Debug>) {
+    let mut machine = Machine::with_memory(memory);
+    machine.run().unwrap();
+    assert_eq!(machine.cpu.registers[0], 0);
+}
+
+fn file(program: Program) -> FileMemory {
+    let file = InMemoryMemory::builder()
+        .bytes(&program())
+        .to_tmp_file()
+        .unwrap();
+    FileMemory::with_file(file)
+}
+
+fn in_memory(c: &mut Criterion) {
+    let mut group = c.benchmark_group("in_memory");
+    for (name, program) in PROGRAMS {
+        group.bench_function(format!("{name}/uncached"), |b| {
+            b.iter(|| run(InMemoryMemory::from_vec(program())))
+        });
+        group.bench_function(format!("{name}/cached"), |b| {
+            b.iter(|| {
+                run(CachedMemory::with_memory(InMemoryMemory::from_vec(
+                    program(),
+                )))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn file_memory(c: &mut Criterion) {
+    let mut group = c.benchmark_group("file_memory");
+    group.sample_size(10);
+    for (name, program) in PROGRAMS {
+        group.bench_function(format!("{name}/uncached"), |b| {
+            b.iter_batched(|| file(program), run, BatchSize::PerIteration)
+        });
+        group.bench_function(format!("{name}/cached"), |b| {
+            b.iter_batched(
+                || CachedMemory::with_memory(file(program)),
+                run,
+                BatchSize::PerIteration,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, in_memory, file_memory);
+criterion_main!(benches);
@@ -1,3 +1,6 @@
 pub mod machine;
 pub mod memory;
+pub mod network;
+pub mod scheduler;
+pub mod trap;
 pub mod types;
@@ -1,28 +1,424 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
 use crate::memory::Memory;
-use crate::types::Instruction;
+use crate::network::{Channel, Port};
+use crate::scheduler::ThreadRequest;
+use crate::trap::{Fault, Trap};
+use crate::types::{ReadWriteable, Register};
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1024;
+pub const DEFAULT_MAX_STACK_SIZE: usize = 64 * 1024;
+pub const NUM_REGISTERS: usize = 16;
+
+/// Processor state that lives outside of memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cpu {
+    /// Return addresses of the calls in progress, innermost last.
+    pub call_stack: Vec<usize>,
+    pub max_call_depth: usize,
+    /// The operand stack used by the stack instructions. Values are pushed
+    /// as their little-endian bytes, so the top of the stack is at the end.
+    pub stack: Vec<u8>,
+    /// The maximum size of `stack`, in bytes.
+    pub max_stack_size: usize,
+    /// General-purpose registers. Unlike memory, these can't be touched by
+    /// self-modifying code.
+    pub registers: [u64; NUM_REGISTERS],
+    pub float_registers: [f64; NUM_REGISTERS],
+    /// Where to jump when a fault is raised, by `Fault::code`.
+    pub trap_handlers: BTreeMap<u8, usize>,
+    /// The trap currently being handled, if any.
+    pub trap_frame: Option<TrapFrame>,
+    /// The number of instructions that have completed.
+    pub instruction_count: u64,
+    /// The timer interrupt fires whenever `instruction_count` reaches a
+    /// multiple of this. Zero means the timer is off.
+    pub timer_interval: u64,
+    pub interrupts_enabled: bool,
+    /// Where to jump when the timer fires.
+    pub interrupt_vector: Option<usize>,
+    /// Where the interrupted code continues from, while in an interrupt.
+    pub interrupt_return: Option<usize>,
+    /// The id of the thread this is running, always 0 outside of a
+    /// `Scheduler`.
+    pub thread_id: usize,
+    /// Set by the thread instructions for the scheduler to act on after the
+    /// instruction.
+    pub thread_request: Option<ThreadRequest>,
+    /// Channels to other machines, by port.
+    pub ports: BTreeMap<u8, Port>,
+    /// The port a `Recv` is waiting on. The cursor stays on the `Recv` until
+    /// a message arrives.
+    pub receiving: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrapFrame {
+    pub code: u8,
+    /// The start of the instruction that faulted.
+    pub address: usize,
+    /// Where `ReturnFromTrap` continues from. This is the instruction after
+    /// the one that faulted, or the faulting address itself if the fault
+    /// happened while fetching.
+    pub resume: usize,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu {
+            call_stack: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack: Vec::new(),
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            registers: [0; NUM_REGISTERS],
+            float_registers: [0.0; NUM_REGISTERS],
+            trap_handlers: BTreeMap::new(),
+            trap_frame: None,
+            instruction_count: 0,
+            timer_interval: 0,
+            interrupts_enabled: false,
+            interrupt_vector: None,
+            interrupt_return: None,
+            thread_id: 0,
+            thread_request: None,
+            ports: BTreeMap::new(),
+            receiving: None,
+        }
+    }
+}
+
+impl Cpu {
+    pub fn push_call(&mut self, return_address: usize) -> Result<(), Fault> {
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(Fault::StackOverflow);
+        }
+        self.call_stack.push(return_address);
+        Ok(())
+    }
+
+    pub fn pop_call(&mut self) -> Result<usize, Fault> {
+        self.call_stack.pop().ok_or(Fault::StackUnderflow)
+    }
+
+    pub fn push<T: ReadWriteable>(&mut self, value: T) -> Result<(), Fault> {
+        if self.stack.len() + T::NUM_BYTES > self.max_stack_size {
+            return Err(Fault::StackOverflow);
+        }
+        let mut buffer = [0_u8; 8];
+        value.into_bytes(&mut buffer[0..T::NUM_BYTES]);
+        self.stack.extend_from_slice(&buffer[0..T::NUM_BYTES]);
+        Ok(())
+    }
+
+    pub fn pop<T: ReadWriteable>(&mut self) -> Result<T, Fault> {
+        let start = self
+            .stack
+            .len()
+            .checked_sub(T::NUM_BYTES)
+            .ok_or(Fault::StackUnderflow)?;
+        let value = T::from_bytes(&self.stack[start..]);
+        self.stack.truncate(start);
+        Ok(value)
+    }
+
+    pub fn register(&mut self, register: Register) -> Result<&mut u64, Fault> {
+        self.registers
+            .get_mut(register.0 as usize)
+            .ok_or(Fault::InvalidRegister)
+    }
+
+    pub fn float_register(&mut self, register: Register) -> Result<&mut f64, Fault> {
+        self.float_registers
+            .get_mut(register.0 as usize)
+            .ok_or(Fault::InvalidRegister)
+    }
+
+    /// The channel `port` sends to.
+    pub fn sender(&self, port: u8) -> Result<&Channel, Fault> {
+        self.ports
+            .get(&port)
+            .and_then(Port::sender)
+            .ok_or(Fault::UnknownPort { port })
+    }
+
+    /// The channel `port` receives from.
+    pub fn receiver(&self, port: u8) -> Result<&Channel, Fault> {
+        self.ports
+            .get(&port)
+            .and_then(Port::receiver)
+            .ok_or(Fault::UnknownPort { port })
+    }
+
+    /// Whether a `Recv` is waiting on a port that has no messages yet.
+    pub fn waiting(&self) -> bool {
+        match self.receiving {
+            Some(port) => self.receiver(port).map_or(true, Channel::is_empty),
+            None => false,
+        }
+    }
+}
+
+/// A Rust function that programs can call with `HostCall`. It's given the
+/// memory and the address of its arguments, and its result is written to
+/// the instruction's output.
+pub type HostFunction<Mem> =
+    Box<dyn FnMut(&mut Mem, usize) -> Result<u64, Trap<<Mem as Memory>::Error>>>;
+
+/// The host functions registered on a machine, by id.
+pub struct HostFunctions<Mem: Memory> {
+    functions: BTreeMap<u16, HostFunction<Mem>>,
+}
+
+impl<Mem: Memory> HostFunctions<Mem> {
+    pub fn insert(&mut self, id: u16, function: HostFunction<Mem>) {
+        self.functions.insert(id, function);
+    }
+
+    /// Calls the function registered as `id`. The cursor may be anywhere
+    /// afterwards.
+    pub fn call(&mut self, id: u16, mem: &mut Mem, args: usize) -> Result<u64, Trap<Mem::Error>> {
+        match self.functions.get_mut(&id) {
+            Some(function) => function(mem, args),
+            None => Err(Fault::UnknownHostCall { id }.into()),
+        }
+    }
+}
+
+impl<Mem: Memory> Default for HostFunctions<Mem> {
+    fn default() -> Self {
+        HostFunctions {
+            functions: BTreeMap::new(),
+        }
+    }
+}
 
-#[derive(Debug, Default)]
+impl<Mem: Memory> fmt::Debug for HostFunctions<Mem> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.functions.keys()).finish()
+    }
+}
+
+/// Called after each instruction a `Machine` executes, with the address it
+/// was fetched from and the CPU state it left behind.
+pub type Tracer = Box<dyn FnMut(usize, &Cpu)>;
+
+/// A machine's memory and CPU state, registers included. Host functions
+/// aren't part of it, and connected channels are shared with the machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<Mem> {
+    pub memory: Mem,
+    pub cpu: Cpu,
+}
+
+#[derive(Default)]
 pub struct Machine<Mem: Memory> {
     pub memory: Mem,
+    pub cpu: Cpu,
+    pub host: HostFunctions<Mem>,
+    tracer: Option<Tracer>,
+}
+
+impl<Mem: Memory + fmt::Debug> fmt::Debug for Machine<Mem> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Machine")
+            .field("memory", &self.memory)
+            .field("cpu", &self.cpu)
+            .field("host", &self.host)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<Mem: Memory> Machine<Mem> {
     pub fn with_memory(memory: Mem) -> Self {
-        Machine { memory }
+        Machine {
+            memory,
+            cpu: Cpu::default(),
+            host: HostFunctions::default(),
+            tracer: None,
+        }
     }
 
-    pub fn run(&mut self) -> Result<(), Mem::Error> {
-        while let Some(instruction) = self.memory.read_if_present::<Instruction>()? {
-            instruction.execute(&mut self.memory)?;
-        }
+    /// Registers `function` to be called by `HostCall` instructions with the
+    /// given id, replacing any function already registered with it.
+    pub fn host_call(
+        mut self,
+        id: u16,
+        function: impl FnMut(&mut Mem, usize) -> Result<u64, Trap<Mem::Error>> + 'static,
+    ) -> Self {
+        self.host.insert(id, Box::new(function));
+        self
+    }
+
+    /// Calls `tracer` after every instruction, replacing any tracer already
+    /// set.
+    pub fn trace(mut self, tracer: impl FnMut(usize, &Cpu) + 'static) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
 
+    pub fn max_call_depth(mut self, depth: usize) -> Self {
+        self.cpu.max_call_depth = depth;
+        self
+    }
+
+    pub fn max_stack_size(mut self, size: usize) -> Self {
+        self.cpu.max_stack_size = size;
+        self
+    }
+
+    /// Connects one end of a channel to the given port, replacing whatever it
+    /// was connected to. `Network::connect` does this for both ends.
+    pub fn port(mut self, port: u8, end: Port) -> Self {
+        self.cpu.ports.insert(port, end);
+        self
+    }
+
+    pub fn run(&mut self) -> Result<(), Trap<Mem::Error>> {
+        while self.step()? {
+            // Nothing else runs while this machine waits, so no message can
+            // ever arrive
+            if self.cpu.waiting() {
+                return Err(Fault::Deadlock.into());
+            }
+        }
         Ok(())
     }
+
+    /// Executes a single instruction. Returns `false` once the end of memory
+    /// is reached.
+    pub fn step(&mut self) -> Result<bool, Trap<Mem::Error>> {
+        let address = self.memory.position()?;
+        if !step(&mut self.memory, &mut self.cpu, &mut self.host)? {
+            return Ok(false);
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer(address, &self.cpu);
+        }
+        match self.cpu.thread_request.take() {
+            None | Some(ThreadRequest::Yield) => Ok(true),
+            // Only a `Scheduler` can run more than one thread
+            Some(ThreadRequest::Spawn { .. } | ThreadRequest::Join { .. }) => {
+                Err(Fault::NoScheduler.into())
+            }
+        }
+    }
+}
+
+impl<Mem: Memory + Clone> Machine<Mem> {
+    pub fn snapshot(&self) -> Snapshot<Mem> {
+        Snapshot {
+            memory: self.memory.clone(),
+            cpu: self.cpu.clone(),
+        }
+    }
+
+    /// Puts the machine back into the state it was in when `snapshot` was
+    /// taken.
+    pub fn restore(&mut self, snapshot: Snapshot<Mem>) {
+        self.memory = snapshot.memory;
+        self.cpu = snapshot.cpu;
+    }
+}
+
+/// Executes the instruction at the cursor, including jumping to trap and
+/// interrupt handlers. Returns `false` once the end of memory is reached.
+pub(crate) fn step<Mem: Memory>(
+    memory: &mut Mem,
+    cpu: &mut Cpu,
+    host: &mut HostFunctions<Mem>,
+) -> Result<bool, Trap<Mem::Error>> {
+    let address = memory.position()?;
+    match execute(memory, cpu, host, address) {
+        Ok(true) => tick(memory, cpu)?,
+        Ok(false) => return Ok(false),
+        Err(trap) => {
+            memory.abort_instruction()?;
+            handle_trap(memory, cpu, trap)?
+        }
+    }
+    Ok(true)
+}
+
+/// Executes the instruction at `address`, which must be where the cursor is.
+fn execute<Mem: Memory>(
+    memory: &mut Mem,
+    cpu: &mut Cpu,
+    host: &mut HostFunctions<Mem>,
+    address: usize,
+) -> Result<bool, Trap<Mem::Error>> {
+    let Some(instruction) = memory.fetch().map_err(|err| Trap::from(err).at(address))? else {
+        return Ok(false);
+    };
+    instruction
+        .execute_with(memory, cpu, host)
+        .map_err(|trap| trap.in_instruction(instruction).at(address))?;
+    memory.end_instruction()?;
+    // A `Recv` that's still waiting hasn't completed
+    if cpu.receiving.is_none() {
+        cpu.instruction_count += 1;
+    }
+    Ok(true)
+}
+
+/// Fires the timer interrupt if it's due. Interrupts are disabled while the
+/// handler runs, and `ReturnFromInterrupt` enables them again.
+fn tick<Mem: Memory>(memory: &mut Mem, cpu: &mut Cpu) -> Result<(), Trap<Mem::Error>> {
+    if !cpu.interrupts_enabled
+        || cpu.receiving.is_some()
+        || cpu.timer_interval == 0
+        || !cpu.instruction_count.is_multiple_of(cpu.timer_interval)
+        || cpu.interrupt_return.is_some()
+    {
+        return Ok(());
+    }
+    let Some(vector) = cpu.interrupt_vector else {
+        return Ok(());
+    };
+
+    cpu.interrupt_return = Some(memory.position()?);
+    cpu.interrupts_enabled = false;
+    memory.set_position(vector)?;
+    Ok(())
+}
+
+/// Jumps to the program's handler for a fault, or gives the trap back if
+/// there isn't one. Faults inside a handler are never handled.
+fn handle_trap<Mem: Memory>(
+    memory: &mut Mem,
+    cpu: &mut Cpu,
+    trap: Trap<Mem::Error>,
+) -> Result<(), Trap<Mem::Error>> {
+    let Trap::Fault {
+        fault,
+        instruction,
+        address: Some(address),
+    } = trap
+    else {
+        return Err(trap);
+    };
+    let handler = match cpu.trap_handlers.get(&fault.code()) {
+        Some(&handler) if cpu.trap_frame.is_none() => handler,
+        _ => return Err(trap),
+    };
+
+    let resume = match instruction {
+        Some(instruction) => address + instruction.encoded_len() + instruction.operand_len(),
+        None => address,
+    };
+    cpu.trap_frame = Some(TrapFrame {
+        code: fault.code(),
+        address,
+        resume,
+    });
+    memory.set_position(handler)?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::memory::InMemoryMemory;
+    use crate::memory::{Access, InMemoryMemory, ProtectedMemory, Protection};
+    use crate::types::{Instruction, Offset, OffsetPair};
 
     use super::*;
 
@@ -75,4 +471,307 @@ mod tests {
         assert_eq!(machine.memory.memory.len(), 100 + 5);
         assert_eq!(machine.memory.memory[101..105], [0x00, 0x00, 0xFC, 0xFF]);
     }
+
+    #[test]
+    fn writing_to_protected_memory_raises_protection_fault() {
+        let memory = ProtectedMemory::with_memory(InMemoryMemory::from_vec(vec![
+            Instruction::Noop as u8,
+            Instruction::Move1 as u8,
+            0xFC,
+            0xFF,
+            0xFE,
+            0xFF,
+        ]))
+        .protect(0..6, Protection::READ_ONLY);
+        let mut machine = Machine::with_memory(memory);
+
+        match machine.run() {
+            Err(Trap::Fault {
+                fault,
+                instruction,
+                address,
+            }) => {
+                assert_eq!(
+                    fault,
+                    Fault::Protection {
+                        address: 1,
+                        access: Access::Write
+                    }
+                );
+                assert_eq!(instruction, Some(Instruction::Move1));
+                assert_eq!(address, Some(1));
+            }
+            other => panic!("expected protection fault, got {:?}", other),
+        }
+        assert_eq!(machine.memory.memory.memory[1], Instruction::Move1 as u8);
+    }
+
+    #[test]
+    fn faults_report_the_address_of_the_instruction() {
+        let memory = ProtectedMemory::with_memory(InMemoryMemory::from_vec(vec![0x00; 4]))
+            .protect(3..4, Protection::NO_EXECUTE);
+        let mut machine = Machine::with_memory(memory);
+
+        match machine.run() {
+            Err(Trap::Fault {
+                instruction: None,
+                address: Some(3),
+                ..
+            }) => {}
+            other => panic!("expected fetch fault at 3, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn calls_return_to_the_following_instruction() {
+        // Call a subroutine that copies its first byte into the data byte
+        // before its `Return`, then jump past the end of the program
+        let mut machine = machine(vec![
+            Instruction::Call as u8,
+            0x03,
+            0x00,
+            Instruction::Jump as u8,
+            0x07,
+            0x00,
+            Instruction::Move1 as u8,
+            0xFB,
+            0xFF,
+            0x04,
+            0x00,
+            0x00,
+            Instruction::Return as u8,
+        ]);
+        machine.run().unwrap();
+        assert_eq!(machine.memory.memory[11], Instruction::Move1 as u8);
+        assert!(machine.cpu.call_stack.is_empty());
+        assert_eq!(machine.memory.pc, 13);
+    }
+
+    #[test]
+    fn recursing_too_deeply_raises_stack_overflow() {
+        let mut machine = machine(vec![Instruction::Call as u8, 0xFD, 0xFF]).max_call_depth(8);
+
+        match machine.run() {
+            Err(Trap::Fault {
+                fault: Fault::StackOverflow,
+                instruction: Some(Instruction::Call),
+                address: Some(0),
+            }) => {}
+            other => panic!("expected stack overflow, got {:?}", other),
+        }
+        assert_eq!(machine.cpu.call_stack, vec![3; 8]);
+    }
+
+    #[test]
+    fn returning_without_a_call_raises_stack_underflow() {
+        let mut machine = machine(vec![Instruction::Return as u8]);
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::StackUnderflow,
+                ..
+            })
+        ));
+    }
+
+    fn set_registers() -> InMemoryMemory {
+        InMemoryMemory::builder()
+            .instruction(Instruction::SetRegister, (Register(0), 1_u64))
+            .instruction(Instruction::SetRegister, (Register(0), 2_u64))
+            .build()
+    }
+
+    #[test]
+    fn snapshots_include_registers() {
+        let mut machine = Machine::with_memory(set_registers());
+        machine.step().unwrap();
+        let snapshot = machine.snapshot();
+        machine.run().unwrap();
+        assert_eq!(machine.cpu.registers[0], 2);
+
+        machine.restore(snapshot);
+        assert_eq!(machine.cpu.registers[0], 1);
+        assert_eq!(machine.memory.pc, 11);
+    }
+
+    #[test]
+    fn tracers_see_every_instruction() {
+        let trace = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut machine = Machine::with_memory(set_registers()).trace({
+            let trace = trace.clone();
+            move |address, cpu| trace.borrow_mut().push((address, cpu.registers[0]))
+        });
+        machine.run().unwrap();
+
+        assert_eq!(*trace.borrow(), [(0, 1), (11, 2)]);
+    }
+
+    #[test]
+    fn host_calls_receive_their_arguments_and_write_their_result() {
+        let memory = InMemoryMemory::builder()
+            .instruction(
+                Instruction::HostCall,
+                (7_u16, Offset(0), OffsetPair(Offset(8), Offset(0))),
+            )
+            .data(20_u32)
+            .data(22_u32)
+            .data(0_u64)
+            .build();
+        let mut machine = Machine::with_memory(memory).host_call(7, |mem, args| {
+            mem.set_position(args)?;
+            let (left, right) = mem.read::<(u32, u32)>()?;
+            Ok(left as u64 + right as u64)
+        });
+        machine.run().unwrap();
+
+        assert_eq!(machine.memory.memory[17..25], 42_u64.to_le_bytes());
+        assert_eq!(machine.memory.pc, 25);
+    }
+
+    #[test]
+    fn unknown_host_calls_trap() {
+        let memory = InMemoryMemory::builder()
+            .instruction(
+                Instruction::HostCall,
+                (7_u16, Offset(0), OffsetPair(Offset(0), Offset(0))),
+            )
+            .build();
+        let mut machine = Machine::with_memory(memory).host_call(8, |_, _| Ok(0));
+
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::UnknownHostCall { id: 7 },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn trap_handlers_can_resume_after_the_faulting_instruction() {
+        let memory = InMemoryMemory::builder()
+            .instruction(Instruction::SetTrapHandler, (7_u8, Offset(24)))
+            .instruction(
+                Instruction::DivideUnsignedInteger64,
+                (1_u64, 0_u64, OffsetPair(Offset(0), Offset(0))),
+            )
+            .instruction(Instruction::Jump, Offset(10))
+            // The handler records where the fault happened and returns
+            .instruction(
+                Instruction::StoreTrapAddress,
+                OffsetPair(Offset(1), Offset(-5)),
+            )
+            .instruction(Instruction::ReturnFromTrap, ())
+            .data(0_u32)
+            .build();
+        let mut machine = Machine::with_memory(memory);
+        machine.run().unwrap();
+
+        assert_eq!(machine.memory.memory[34..38], 4_u32.to_le_bytes());
+        assert_eq!(machine.cpu.trap_frame, None);
+        assert_eq!(machine.memory.pc, 38);
+    }
+
+    #[test]
+    fn invalid_opcodes_trap() {
+        let mut machine = machine(vec![0x00, 0x9F]);
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::InvalidOpcode,
+                address: Some(1),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn truncated_extended_opcodes_can_be_handled() {
+        // The handler is the end of memory, just after the lone prefix byte
+        let memory = InMemoryMemory::builder()
+            .instruction(
+                Instruction::SetTrapHandler,
+                (Fault::InvalidOpcode.code(), Offset(1)),
+            )
+            .bytes(&[0xF5])
+            .build();
+        let mut machine = Machine::with_memory(memory);
+        machine.run().unwrap();
+
+        let frame = machine.cpu.trap_frame.unwrap();
+        assert_eq!(frame.code, Fault::InvalidOpcode.code());
+        assert_eq!(frame.address, 4);
+    }
+
+    #[test]
+    fn faults_inside_a_trap_handler_are_not_handled() {
+        let memory = InMemoryMemory::builder()
+            .instruction(Instruction::SetTrapHandler, (9_u8, Offset(1)))
+            .instruction(Instruction::SoftwareInterrupt, ())
+            .instruction(Instruction::SoftwareInterrupt, ())
+            .build();
+        let mut machine = Machine::with_memory(memory);
+
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::SoftwareInterrupt,
+                address: Some(5),
+                ..
+            })
+        ));
+        assert_eq!(
+            machine.cpu.trap_frame,
+            Some(TrapFrame {
+                code: 9,
+                address: 4,
+                resume: 5
+            })
+        );
+    }
+
+    #[test]
+    fn timer_interrupts_fire_every_interval() {
+        let memory = InMemoryMemory::builder()
+            .instruction(Instruction::SetInterruptVector, Offset(19))
+            .instruction(Instruction::SetTimer, 4_u64)
+            .instruction(Instruction::EnableInterrupts, ())
+            .instruction(Instruction::Noop, ())
+            .instruction(Instruction::Noop, ())
+            .instruction(Instruction::Noop, ())
+            .instruction(Instruction::Noop, ())
+            .instruction(Instruction::Jump, Offset(16))
+            // The handler records when it last ran
+            .instruction(
+                Instruction::StoreInstructionCount,
+                OffsetPair(Offset(2), Offset(-10)),
+            )
+            .instruction(Instruction::ReturnFromInterrupt, ())
+            .data(0_u64)
+            .build();
+        let mut machine = Machine::with_memory(memory);
+        machine.run().unwrap();
+
+        // It fires after the 4th, 8th and 12th instructions, the last being
+        // the jump past the handler
+        assert_eq!(machine.memory.memory[31..39], 12_u64.to_le_bytes());
+        assert_eq!(machine.cpu.instruction_count, 14);
+        assert!(machine.cpu.interrupts_enabled);
+        assert_eq!(machine.memory.pc, 39);
+    }
+
+    #[test]
+    fn returning_from_interrupt_outside_of_one_faults() {
+        let memory = InMemoryMemory::builder()
+            .instruction(Instruction::ReturnFromInterrupt, ())
+            .build();
+        let mut machine = Machine::with_memory(memory);
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::NotHandlingInterrupt,
+                ..
+            })
+        ));
+    }
 }
@@ -3,20 +3,52 @@ use std::path::PathBuf;
 use clap::Parser;
 
 use esolang::machine;
-use esolang::memory;
+use esolang::memory::{self, Memory};
+use esolang::trap::Trap;
 
 #[derive(Debug, clap::Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     name: PathBuf,
+
+    /// Write changes the program makes to itself back to the file. By
+    /// default the file is left untouched.
+    #[arg(long)]
+    in_place: bool,
+
+    /// Journal writes so that a crash never leaves the file with a
+    /// half-executed instruction in it.
+    #[arg(long, requires = "in_place")]
+    journal: bool,
+
+    /// Cache decoded instructions, so that loops don't read them from the
+    /// file again on every pass.
+    #[arg(long)]
+    cache: bool,
 }
 
-fn main() -> Result<(), std::io::Error> {
-    let args = Args::parse();
+fn run<Mem: Memory>(memory: Mem, cache: bool) -> Result<(), Trap<Mem::Error>> {
+    if cache {
+        let memory = memory::CachedMemory::with_memory(memory);
+        machine::Machine::with_memory(memory).run()
+    } else {
+        machine::Machine::with_memory(memory).run()
+    }
+}
 
-    let memory = memory::FileMemory::with_path(args.name).unwrap();
-    let mut machine = machine::Machine::with_memory(memory);
+fn main() -> Result<(), Trap<std::io::Error>> {
+    let args = Args::parse();
 
-    machine.run()?;
+    if args.in_place {
+        let memory = if args.journal {
+            memory::FileMemory::with_journal(args.name).unwrap()
+        } else {
+            memory::FileMemory::with_path(args.name).unwrap()
+        };
+        run(memory, args.cache)?;
+    } else {
+        let memory = memory::OverlayMemory::with_path(args.name).unwrap();
+        run(memory, args.cache)?;
+    }
     Ok(())
 }
@@ -0,0 +1,311 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::types::{Instruction, Offset, ReadWriteable};
+
+use super::{AtomicValue, Memory};
+
+/// An instruction as it was fetched: its opcode, and the bytes of its
+/// operands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Decoded {
+    instruction: Instruction,
+    /// Where the operands start. This is usually just after the opcode, but
+    /// an unknown one byte opcode decodes as the two byte `InvalidOpcode`.
+    operands_at: usize,
+    /// Shared with `current` while the instruction executes.
+    operands: Rc<[u8]>,
+}
+
+impl Decoded {
+    fn end(&self) -> usize {
+        self.operands_at + self.operands.len()
+    }
+}
+
+/// Wraps another memory and remembers the instructions fetched from it, by
+/// address. Fetching a cached instruction, and reading its operands, doesn't
+/// touch the wrapped memory at all, which saves a read per operand (a
+/// syscall each for `FileMemory`). Over an `InMemoryMemory` it only pays off
+/// for code that doesn't modify itself, since every rewritten instruction has
+/// to be fetched and cached again (see `benches/instruction_cache.rs`).
+///
+/// Writes through the cache drop every cached instruction they overlap, so
+/// self-modifying code behaves as it would without the cache. Changes made
+/// to the wrapped memory some other way (e.g. through a clone of a
+/// `SharedMemory`) aren't noticed until `clear` is called.
+///
+/// Reading cached operands doesn't move the wrapped memory's cursor, so the
+/// cursor is kept here and only handed on when the wrapped memory is used.
+/// Addresses are the same in both, so the wrapped memory is expected to
+/// start at address 0.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CachedMemory<Mem: Memory> {
+    pub memory: Mem,
+    pos: usize,
+    /// Whether the wrapped memory's cursor is at `pos`. It's only moved when
+    /// it's about to be used.
+    synced: bool,
+    instructions: BTreeMap<usize, Decoded>,
+    /// The longest instruction ever cached, which bounds how far before a
+    /// write an overlapping instruction can start.
+    longest: usize,
+    /// The instruction being executed, if it's cached.
+    current: Option<Decoded>,
+}
+
+impl<Mem: Memory> CachedMemory<Mem> {
+    pub fn with_memory(memory: Mem) -> Self {
+        CachedMemory {
+            memory,
+            pos: 0,
+            synced: false,
+            instructions: BTreeMap::new(),
+            longest: 0,
+            current: None,
+        }
+    }
+
+    /// The number of instructions in the cache.
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Forgets every cached instruction.
+    pub fn clear(&mut self) {
+        self.instructions.clear();
+        self.current = None;
+    }
+
+    fn sync(&mut self) -> Result<(), Mem::Error> {
+        if !self.synced {
+            self.memory.set_position(self.pos)?;
+            self.synced = true;
+        }
+        Ok(())
+    }
+
+    /// The bytes at the cursor, if they're operands of the instruction being
+    /// executed.
+    fn cached_operands(&self, len: usize) -> Option<&[u8]> {
+        let decoded = self.current.as_ref()?;
+        let start = self.pos.checked_sub(decoded.operands_at)?;
+        decoded.operands.get(start..start + len)
+    }
+
+    fn invalidate(&mut self, written: Range<usize>) {
+        if self.current.as_ref().is_some_and(|decoded| {
+            decoded.end() > written.start && decoded.operands_at < written.end
+        }) {
+            self.current = None;
+        }
+        let from = written.start.saturating_sub(self.longest);
+        while let Some(address) = self
+            .instructions
+            .range(from..written.end)
+            .find(|(_, decoded)| decoded.end() > written.start)
+            .map(|(&address, _)| address)
+        {
+            self.instructions.remove(&address);
+        }
+    }
+
+    /// Fetches from the wrapped memory, and caches the instruction if all of
+    /// its operands are there.
+    fn fetch_uncached(&mut self) -> Result<Option<Instruction>, Mem::Error> {
+        let address = self.pos;
+        self.sync()?;
+        let Some(instruction) = self.memory.fetch()? else {
+            return Ok(None);
+        };
+        let operands_at = self.memory.position()?;
+
+        // Read in as few chunks as possible, as each read may be a syscall
+        let len = instruction.operand_len();
+        let mut operands = Vec::with_capacity(len);
+        while operands.len() < len {
+            let present = match len - operands.len() {
+                8.. => read_chunk::<_, 8>(&mut self.memory, &mut operands)?,
+                4..=7 => read_chunk::<_, 4>(&mut self.memory, &mut operands)?,
+                2..=3 => read_chunk::<_, 2>(&mut self.memory, &mut operands)?,
+                _ => read_chunk::<_, 1>(&mut self.memory, &mut operands)?,
+            };
+            if !present {
+                break;
+            }
+        }
+        self.pos = operands_at;
+        self.synced = false;
+        if operands.len() < len {
+            // Leave it to the instruction to fail reading its operands
+            self.current = None;
+            return Ok(Some(instruction));
+        }
+
+        let decoded = Decoded {
+            instruction,
+            operands_at,
+            operands: operands.into(),
+        };
+        self.longest = self.longest.max(decoded.end() - address);
+        self.instructions.insert(address, decoded.clone());
+        self.current = Some(decoded);
+        Ok(Some(instruction))
+    }
+}
+
+/// Appends the next `N` bytes to `operands`, unless they're past the end of
+/// memory.
+fn read_chunk<Mem: Memory, const N: usize>(
+    memory: &mut Mem,
+    operands: &mut Vec<u8>,
+) -> Result<bool, Mem::Error> {
+    match memory.read_if_present::<[u8; N]>()? {
+        Some(bytes) => {
+            operands.extend_from_slice(&bytes);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+impl<Mem: Memory> Memory for CachedMemory<Mem> {
+    type Error = Mem::Error;
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        if let Some(bytes) = self.cached_operands(T::NUM_BYTES) {
+            let value = T::from_bytes(bytes);
+            self.pos += T::NUM_BYTES;
+            self.synced = false;
+            return Ok(value);
+        }
+        self.sync()?;
+        let value = self.memory.read()?;
+        self.pos += T::NUM_BYTES;
+        Ok(value)
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        if self.cached_operands(T::NUM_BYTES).is_some() {
+            return self.read().map(Some);
+        }
+        self.sync()?;
+        let value = self.memory.read_if_present()?;
+        if value.is_some() {
+            self.pos += T::NUM_BYTES;
+        }
+        Ok(value)
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        self.sync()?;
+        self.memory.write(value)?;
+        self.invalidate(self.pos..self.pos + T::NUM_BYTES);
+        self.pos += T::NUM_BYTES;
+        Ok(())
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.pos = self.pos.wrapping_add_signed(pos.0 as isize);
+        self.synced = false;
+        Ok(())
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.pos)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.pos = address;
+        self.synced = false;
+        Ok(())
+    }
+
+    fn fetch(&mut self) -> Result<Option<Instruction>, Self::Error> {
+        let Some(decoded) = self.instructions.get(&self.pos) else {
+            return self.fetch_uncached();
+        };
+        let instruction = decoded.instruction;
+        self.pos = decoded.operands_at;
+        self.synced = false;
+        self.current = Some(decoded.clone());
+        Ok(Some(instruction))
+    }
+
+    fn end_instruction(&mut self) -> Result<(), Self::Error> {
+        self.current = None;
+        self.memory.end_instruction()
+    }
+
+    fn abort_instruction(&mut self) -> Result<(), Self::Error> {
+        self.current = None;
+        self.memory.abort_instruction()
+    }
+
+    fn atomic_update<T: AtomicValue>(
+        &mut self,
+        update: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, Self::Error> {
+        self.sync()?;
+        let old = self.memory.atomic_update(update)?;
+        self.invalidate(self.pos..self.pos + T::NUM_BYTES);
+        self.pos += T::NUM_BYTES;
+        Ok(old)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::Machine;
+    use crate::memory::InMemoryMemory;
+    use crate::types::OffsetPair;
+
+    use super::*;
+
+    #[test]
+    fn self_modifying_code_sees_its_own_writes() {
+        // The first instruction decides whether the JumpIf goes round again,
+        // and the second changes the first so that it won't. With stale
+        // instructions in the cache, this would never stop.
+        let program = InMemoryMemory::builder()
+            .instruction(
+                Instruction::AddInteger8,
+                (1_u8, 0_u8, OffsetPair(Offset(8), Offset(-9))),
+            )
+            .instruction(
+                Instruction::AddInteger8,
+                (0_u8, 0_u8, OffsetPair(Offset(-13), Offset(12))),
+            )
+            .instruction(Instruction::JumpIf, (false, Offset(-18)))
+            .build();
+        let mut machine = Machine::with_memory(CachedMemory::with_memory(program));
+        machine.run().unwrap();
+
+        assert_eq!(machine.cpu.instruction_count, 6);
+        assert_eq!(machine.memory.memory.memory[1], 0);
+        // The first instruction was rewritten again after it last ran
+        assert_eq!(machine.memory.len(), 2);
+    }
+
+    #[test]
+    fn writes_drop_the_instructions_they_overlap() {
+        let program = InMemoryMemory::builder()
+            .instruction(Instruction::Jump, Offset(0))
+            .instruction(Instruction::Jump, Offset(0))
+            .instruction(Instruction::Jump, Offset(0))
+            .build();
+        let mut machine = Machine::with_memory(CachedMemory::with_memory(program));
+        machine.run().unwrap();
+        assert_eq!(machine.memory.len(), 3);
+
+        machine.memory.set_position(5).unwrap();
+        machine.memory.write(0_u16).unwrap();
+        assert_eq!(machine.memory.len(), 1);
+        assert_eq!(machine.memory.instructions.keys().collect::<Vec<_>>(), [&0]);
+    }
+}
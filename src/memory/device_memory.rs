@@ -0,0 +1,442 @@
+use std::{
+    fmt::Debug,
+    io::{self, Read, Stdin, Stdout, Write},
+    ops::Range,
+    time::Instant,
+};
+
+use crate::trap::Fault;
+use crate::types::{Offset, ReadWriteable};
+
+use super::{AtomicValue, Memory, MemoryError};
+
+/// Something that can be mapped into a `DeviceMemory` address range.
+///
+/// `offset` is relative to the start of the range the device is mapped at.
+pub trait Device: Debug {
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> io::Result<()>;
+    fn write(&mut self, offset: usize, buffer: &[u8]) -> io::Result<()>;
+}
+
+/// Writes every byte written to it to the output. Reads always return zero.
+#[derive(Debug)]
+pub struct Console<W: Write> {
+    output: W,
+}
+
+impl<W: Write> Console<W> {
+    pub fn with_output(output: W) -> Self {
+        Console { output }
+    }
+}
+
+impl Console<Stdout> {
+    pub fn stdout() -> Self {
+        Console::with_output(io::stdout())
+    }
+}
+
+impl<W: Write + Debug> Device for Console<W> {
+    fn read(&mut self, _offset: usize, buffer: &mut [u8]) -> io::Result<()> {
+        buffer.fill(0);
+        Ok(())
+    }
+
+    fn write(&mut self, _offset: usize, buffer: &[u8]) -> io::Result<()> {
+        self.output.write_all(buffer)?;
+        self.output.flush()
+    }
+}
+
+/// Consumes bytes from the input on every read. Once the input is exhausted,
+/// reads return zero. Writes are ignored.
+#[derive(Debug)]
+pub struct Keyboard<R: Read> {
+    input: R,
+}
+
+impl<R: Read> Keyboard<R> {
+    pub fn with_input(input: R) -> Self {
+        Keyboard { input }
+    }
+}
+
+impl Keyboard<Stdin> {
+    pub fn stdin() -> Self {
+        Keyboard::with_input(io::stdin())
+    }
+}
+
+impl<R: Read + Debug> Device for Keyboard<R> {
+    fn read(&mut self, _offset: usize, buffer: &mut [u8]) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.input.read(&mut buffer[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        buffer[filled..].fill(0);
+        Ok(())
+    }
+
+    fn write(&mut self, _offset: usize, _buffer: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Exposes the nanoseconds elapsed since the clock was created as a
+/// little-endian u64 in the first 8 bytes of its region. Writes are ignored.
+#[derive(Debug)]
+pub struct Clock {
+    start: Instant,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new()
+    }
+}
+
+impl Device for Clock {
+    fn read(&mut self, offset: usize, buffer: &mut [u8]) -> io::Result<()> {
+        let elapsed = (self.start.elapsed().as_nanos() as u64).to_le_bytes();
+        for (index, byte) in buffer.iter_mut().enumerate() {
+            *byte = elapsed.get(offset + index).copied().unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, _offset: usize, _buffer: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum DeviceError<E> {
+    Device(io::Error),
+    Memory(E),
+}
+
+impl<E: MemoryError> MemoryError for DeviceError<E> {
+    fn fault(&self) -> Option<Fault> {
+        match self {
+            DeviceError::Device(_) => None,
+            DeviceError::Memory(err) => err.fault(),
+        }
+    }
+}
+
+/// Routes accesses to mapped devices, and everything else to the backing
+/// memory.
+///
+/// Devices are mapped at the backing memory's addresses, and its cursor is
+/// the only one: accesses to a device skip over the bytes underneath, which
+/// are never touched.
+#[derive(Debug)]
+pub struct DeviceMemory<Mem: Memory> {
+    pub memory: Mem,
+    devices: Vec<(Range<usize>, Box<dyn Device>)>,
+}
+
+impl<Mem: Memory> DeviceMemory<Mem> {
+    pub fn with_memory(memory: Mem) -> Self {
+        DeviceMemory {
+            memory,
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn map(mut self, range: Range<usize>, device: impl Device + 'static) -> Self {
+        self.devices.push((range, Box::new(device)));
+        self
+    }
+
+    fn touches_device(&self, len: usize) -> Result<bool, DeviceError<Mem::Error>> {
+        let position = self.memory.position().map_err(DeviceError::Memory)?;
+        let accessed = position..position + len;
+        Ok(self
+            .devices
+            .iter()
+            .any(|(range, _)| range.start < accessed.end && accessed.start < range.end))
+    }
+
+    /// Splits the access at `pos` into the next run of bytes that either
+    /// belongs to a single device, or to the backing memory.
+    fn next_segment(&self, pos: usize, end: usize) -> (Option<usize>, Range<usize>) {
+        let device = self
+            .devices
+            .iter()
+            .position(|(range, _)| range.contains(&pos));
+        match device {
+            Some(index) => {
+                let range = &self.devices[index].0;
+                (Some(index), pos..end.min(range.end))
+            }
+            None => {
+                let next_device = self
+                    .devices
+                    .iter()
+                    .map(|(range, _)| range.start)
+                    .filter(|&start| start > pos)
+                    .min()
+                    .unwrap_or(end);
+                (None, pos..end.min(next_device))
+            }
+        }
+    }
+
+    fn skip_memory(&mut self, len: usize) -> Result<(), DeviceError<Mem::Error>> {
+        let mut remaining = len;
+        while remaining > 0 {
+            let step = remaining.min(i16::MAX as usize);
+            self.memory
+                .seek(Offset(step as i16))
+                .map_err(DeviceError::Memory)?;
+            remaining -= step;
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), DeviceError<Mem::Error>> {
+        let start = self.memory.position().map_err(DeviceError::Memory)?;
+        let end = start + buffer.len();
+        let mut pos = start;
+        while pos < end {
+            let (device, segment) = self.next_segment(pos, end);
+            let bytes = &mut buffer[segment.start - start..segment.end - start];
+            match device {
+                Some(index) => {
+                    let (range, device) = &mut self.devices[index];
+                    device
+                        .read(segment.start - range.start, bytes)
+                        .map_err(DeviceError::Device)?;
+                    self.skip_memory(bytes.len())?;
+                }
+                None => {
+                    for byte in bytes.iter_mut() {
+                        *byte = self.memory.read().map_err(DeviceError::Memory)?;
+                    }
+                }
+            }
+            pos = segment.end;
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, buffer: &[u8]) -> Result<(), DeviceError<Mem::Error>> {
+        let start = self.memory.position().map_err(DeviceError::Memory)?;
+        let end = start + buffer.len();
+        let mut pos = start;
+        while pos < end {
+            let (device, segment) = self.next_segment(pos, end);
+            let bytes = &buffer[segment.start - start..segment.end - start];
+            match device {
+                Some(index) => {
+                    let (range, device) = &mut self.devices[index];
+                    device
+                        .write(segment.start - range.start, bytes)
+                        .map_err(DeviceError::Device)?;
+                    self.skip_memory(bytes.len())?;
+                }
+                None => {
+                    for &byte in bytes {
+                        self.memory.write(byte).map_err(DeviceError::Memory)?;
+                    }
+                }
+            }
+            pos = segment.end;
+        }
+        Ok(())
+    }
+}
+
+impl<Mem: Memory> Memory for DeviceMemory<Mem> {
+    type Error = DeviceError<Mem::Error>;
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        if !self.touches_device(T::NUM_BYTES)? {
+            return self.memory.read().map_err(DeviceError::Memory);
+        }
+
+        let mut buffer = [0_u8; 8];
+        self.read_bytes(&mut buffer[0..T::NUM_BYTES])?;
+        Ok(T::from_bytes(&buffer[0..T::NUM_BYTES]))
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        // Devices are always present, so only plain memory can run out
+        if self.touches_device(T::NUM_BYTES)? {
+            return self.read().map(Some);
+        }
+        self.memory.read_if_present().map_err(DeviceError::Memory)
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        if !self.touches_device(T::NUM_BYTES)? {
+            return self.memory.write(value).map_err(DeviceError::Memory);
+        }
+
+        let mut buffer = [0_u8; 8];
+        value.into_bytes(&mut buffer[0..T::NUM_BYTES]);
+        self.write_bytes(&buffer[0..T::NUM_BYTES])
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.memory.seek(pos).map_err(DeviceError::Memory)
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        self.memory.position().map_err(DeviceError::Memory)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.memory
+            .set_position(address)
+            .map_err(DeviceError::Memory)
+    }
+
+    fn end_instruction(&mut self) -> Result<(), Self::Error> {
+        self.memory.end_instruction().map_err(DeviceError::Memory)
+    }
+
+    fn abort_instruction(&mut self) -> Result<(), Self::Error> {
+        self.memory.abort_instruction().map_err(DeviceError::Memory)
+    }
+
+    fn atomic_update<T: AtomicValue>(
+        &mut self,
+        mut update: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, Self::Error> {
+        if !self.touches_device(T::NUM_BYTES)? {
+            return self
+                .memory
+                .atomic_update(update)
+                .map_err(DeviceError::Memory);
+        }
+
+        let current = self.read::<T>()?;
+        if let Some(new) = update(current) {
+            self.seek(Offset(-(T::NUM_BYTES as i16)))?;
+            self.write(new)?;
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use crate::machine::Machine;
+    use crate::memory::InMemoryMemory;
+    use crate::types::Instruction;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn accesses_outside_devices_go_to_backing_memory() {
+        let mut mem = DeviceMemory::with_memory(InMemoryMemory::from_vec(vec![0x01, 0x02]))
+            .map(4..8, Clock::new());
+        assert_eq!(mem.read::<u8>().unwrap(), 0x01);
+        mem.write(0xFF_u8).unwrap();
+        assert_eq!(mem.memory.memory, vec![0x01, 0xFF]);
+    }
+
+    #[test]
+    fn writing_to_console_region_prints() {
+        let output = SharedOutput::default();
+        let mut mem = DeviceMemory::with_memory(InMemoryMemory::from_vec(vec![0x00; 4]))
+            .map(2..3, Console::with_output(output.clone()));
+        mem.seek(Offset(2)).unwrap();
+        mem.write(b'h').unwrap();
+        mem.seek(Offset(-1)).unwrap();
+        mem.write(b'i').unwrap();
+
+        assert_eq!(*output.0.borrow(), b"hi");
+        assert_eq!(mem.memory.memory, vec![0x00; 4]);
+    }
+
+    #[test]
+    fn reading_from_keyboard_region_consumes_input() {
+        let mut mem = DeviceMemory::with_memory(InMemoryMemory::from_vec(vec![0x00; 4]))
+            .map(0..1, Keyboard::with_input(Cursor::new(b"ab".to_vec())));
+        assert_eq!(mem.read::<u8>().unwrap(), b'a');
+        mem.seek(Offset(-1)).unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), b'b');
+        mem.seek(Offset(-1)).unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), 0);
+    }
+
+    #[test]
+    fn accesses_can_straddle_devices_and_memory() {
+        let output = SharedOutput::default();
+        let mut mem = DeviceMemory::with_memory(InMemoryMemory::from_vec(vec![0x00; 4]))
+            .map(1..3, Console::with_output(output.clone()));
+        mem.write(0x04030201_u32).unwrap();
+
+        assert_eq!(*output.0.borrow(), vec![0x02, 0x03]);
+        assert_eq!(mem.memory.memory, vec![0x01, 0x00, 0x00, 0x04]);
+    }
+
+    #[test]
+    fn devices_use_the_backing_memorys_addresses() {
+        let output = SharedOutput::default();
+        let mut inner = InMemoryMemory::from_vec(vec![0x00; 4]);
+        inner.pc = 2;
+        let mut mem =
+            DeviceMemory::with_memory(inner).map(2..3, Console::with_output(output.clone()));
+        assert_eq!(mem.position().unwrap(), 2);
+        mem.write(b'h').unwrap();
+
+        assert_eq!(*output.0.borrow(), b"h");
+        assert_eq!(mem.memory.pc, 3);
+    }
+
+    #[test]
+    fn clock_region_reads_elapsed_time() {
+        let mut mem = DeviceMemory::with_memory(InMemoryMemory::default()).map(0..8, Clock::new());
+        let first = mem.read::<u64>().unwrap();
+        mem.seek(Offset(-8)).unwrap();
+        let second = mem.read::<u64>().unwrap();
+        assert!(second >= first);
+    }
+
+    #[test]
+    fn programs_can_print_through_console_region() {
+        let output = SharedOutput::default();
+        let program = InMemoryMemory::builder()
+            .instruction(Instruction::Move1, (Offset(0), Offset(0)))
+            .byte(b'!')
+            .byte(0x00)
+            .build();
+        let memory =
+            DeviceMemory::with_memory(program).map(6..7, Console::with_output(output.clone()));
+        let mut machine = Machine::with_memory(memory);
+        machine.run().unwrap();
+
+        assert_eq!(*output.0.borrow(), b"!");
+    }
+}
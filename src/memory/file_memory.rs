@@ -1,31 +1,242 @@
 use std::{
-    fs::File,
-    io::{Read, Result as IoResult, Seek, SeekFrom, Write},
-    path::Path,
+    ffi::OsString,
+    fs::{self, File},
+    io::{ErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
 use crate::types::{Offset, ReadWriteable};
 
-use super::Memory;
+use super::{Cursor, Memory, Storage};
+
+const JOURNAL_WRITE: u8 = 0x01;
+const JOURNAL_COMMIT: u8 = 0x02;
 
 #[derive(Debug)]
 pub struct FileMemory {
     file: File,
+    journal: Option<Journal>,
+    position: usize,
+}
+
+/// Write-ahead log used in journal mode. Writes are kept in `pending` until
+/// the end of the instruction, then logged to the journal file, applied to
+/// the image, and finally cleared from the journal.
+#[derive(Debug)]
+struct Journal {
+    file: File,
+    pending: Vec<(u64, Vec<u8>)>,
 }
 
 impl FileMemory {
+    /// Opens the image at `file`. If a journal was left behind by a crashed
+    /// journal-mode run, the image is first recovered to the end of the last
+    /// complete instruction.
     pub fn with_path(file: impl AsRef<Path>) -> IoResult<Self> {
+        let mut image = open_image(file.as_ref())?;
+        let journal_path = journal_path(file.as_ref());
+        if journal_path.exists() {
+            recover(&mut image, &journal_path)?;
+            fs::remove_file(&journal_path)?;
+        }
+
+        Ok(FileMemory {
+            file: image,
+            journal: None,
+            position: 0,
+        })
+    }
+
+    /// Like `with_path`, but writes are journaled so that each instruction's
+    /// writes reach the image all at once or not at all. The journal lives
+    /// next to the image, with `.journal` appended to the name.
+    pub fn with_journal(file: impl AsRef<Path>) -> IoResult<Self> {
+        let mut image = open_image(file.as_ref())?;
+        let journal_path = journal_path(file.as_ref());
+        if journal_path.exists() {
+            recover(&mut image, &journal_path)?;
+        }
+
         Ok(FileMemory {
-            file: File::options()
-                .read(true)
-                .write(true)
-                .create(true)
-                .open(file.as_ref())?,
+            file: image,
+            journal: Some(Journal {
+                file: File::options()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(journal_path)?,
+                pending: Vec::new(),
+            }),
+            position: 0,
         })
     }
 
     pub fn with_file(file: File) -> Self {
-        Self { file }
+        Self {
+            file,
+            journal: None,
+            position: 0,
+        }
+    }
+
+    /// Runs `f` on a cursor over the image at `position`, then moves
+    /// `position` to wherever the cursor ended up.
+    fn with_cursor<R>(&mut self, f: impl FnOnce(&mut Cursor<&mut Self>) -> R) -> R {
+        let mut cursor = Cursor {
+            position: self.position,
+            storage: &mut *self,
+        };
+        let result = f(&mut cursor);
+        self.position = cursor.position;
+        result
+    }
+
+    /// Length of the image, including writes that haven't been committed yet.
+    fn journaled_len(&self, journal: &Journal) -> IoResult<u64> {
+        let pending_end = journal
+            .pending
+            .iter()
+            .map(|(address, bytes)| address + bytes.len() as u64)
+            .max()
+            .unwrap_or(0);
+        Ok(self.file.metadata()?.len().max(pending_end))
+    }
+
+    /// Reads at `address`, taking uncommitted writes into account.
+    fn read_journaled(&mut self, address: usize, buffer: &mut [u8]) -> IoResult<bool> {
+        let Some(journal) = &self.journal else {
+            unreachable!("only called in journal mode")
+        };
+
+        let start = address as u64;
+        let end = start + buffer.len() as u64;
+        if end > self.journaled_len(journal)? {
+            return Ok(false);
+        }
+
+        buffer.fill(0);
+        let file_len = self.file.metadata()?.len();
+        if start < file_len {
+            let available = (file_len - start).min(buffer.len() as u64) as usize;
+            self.file.seek(SeekFrom::Start(start))?;
+            self.file.read_exact(&mut buffer[0..available])?;
+        }
+        for (address, bytes) in &journal.pending {
+            for (index, &byte) in bytes.iter().enumerate() {
+                let address = address + index as u64;
+                if (start..end).contains(&address) {
+                    buffer[(address - start) as usize] = byte;
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+fn open_image(path: &Path) -> IoResult<File> {
+    File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+fn journal_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".journal");
+    PathBuf::from(name)
+}
+
+fn append_journal_entry(journal: &mut Vec<u8>, address: u64, bytes: &[u8]) {
+    journal.push(JOURNAL_WRITE);
+    journal.extend_from_slice(&address.to_le_bytes());
+    journal.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    journal.extend_from_slice(bytes);
+}
+
+fn append_journal_commit(journal: &mut Vec<u8>, entries: usize) {
+    journal.push(JOURNAL_COMMIT);
+    journal.extend_from_slice(&(entries as u32).to_le_bytes());
+}
+
+/// Parses a journal, returning its writes only if the commit marker made it
+/// to disk. Anything else means the crash happened before the instruction
+/// was complete, and the journal is ignored.
+fn parse_journal(mut journal: &[u8]) -> Option<Vec<(u64, Vec<u8>)>> {
+    fn take<'a>(journal: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if journal.len() < len {
+            return None;
+        }
+        let (taken, rest) = journal.split_at(len);
+        *journal = rest;
+        Some(taken)
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        match take(&mut journal, 1)?[0] {
+            JOURNAL_WRITE => {
+                let address = u64::from_le_bytes(take(&mut journal, 8)?.try_into().ok()?);
+                let len = u32::from_le_bytes(take(&mut journal, 4)?.try_into().ok()?);
+                entries.push((address, take(&mut journal, len as usize)?.to_vec()));
+            }
+            JOURNAL_COMMIT => {
+                let count = u32::from_le_bytes(take(&mut journal, 4)?.try_into().ok()?);
+                return (count as usize == entries.len()).then_some(entries);
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn apply(image: &mut File, entries: &[(u64, Vec<u8>)]) -> IoResult<()> {
+    for (address, bytes) in entries {
+        image.seek(SeekFrom::Start(*address))?;
+        image.write_all(bytes)?;
+    }
+    image.sync_data()
+}
+
+fn recover(image: &mut File, journal_path: &Path) -> IoResult<()> {
+    let journal = fs::read(journal_path)?;
+    if let Some(entries) = parse_journal(&journal) {
+        apply(image, &entries)?;
+    }
+    Ok(())
+}
+
+impl Storage for FileMemory {
+    type Error = std::io::Error;
+
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if self.journal.is_some() {
+            return match self.read_journaled(address, buffer)? {
+                true => Ok(()),
+                false => Err(ErrorKind::UnexpectedEof.into()),
+            };
+        }
+        self.file.seek(SeekFrom::Start(address as u64))?;
+        self.file.read_exact(buffer)
+    }
+
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error> {
+        match &mut self.journal {
+            Some(journal) => journal.pending.push((address as u64, buffer.to_vec())),
+            None => {
+                self.file.seek(SeekFrom::Start(address as u64))?;
+                self.file.write_all(buffer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize, Self::Error> {
+        let len = match &self.journal {
+            Some(journal) => self.journaled_len(journal)?,
+            None => self.file.metadata()?.len(),
+        };
+        Ok(len as usize)
     }
 }
 
@@ -33,37 +244,142 @@ impl Memory for FileMemory {
     type Error = std::io::Error;
 
     fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
-        // NOTE: With full const generics, we should be able to define the
-        // buffer size as exactly T::NUM_BYTES, which might(?) allow
-        // the compiler to optimize this better and will be clearer
-
-        let mut buffer = [0_u8; 8];
-        self.file.read_exact(&mut buffer[0..T::NUM_BYTES])?;
-        let value = T::from_bytes(&buffer);
-        Ok(value)
+        self.with_cursor(|cursor| cursor.read())
     }
 
     fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
-        let mut buffer = [0_u8; 8];
-        match self.file.read_exact(&mut buffer[0..T::NUM_BYTES]) {
-            Ok(_) => {
-                let value = T::from_bytes(&buffer);
-                Ok(Some(value))
-            }
-            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
-            Err(err) => Err(err),
-        }
+        self.with_cursor(|cursor| cursor.read_if_present())
     }
 
     fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
-        let mut buffer = [0_u8; 8];
-        value.into_bytes(&mut buffer[0..T::NUM_BYTES]);
-        self.file.write_all(&buffer[0..T::NUM_BYTES])?;
-        Ok(())
+        self.with_cursor(|cursor| cursor.write(value))
     }
 
     fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
-        self.file.seek(SeekFrom::Current(pos.0 as i64))?;
+        self.with_cursor(|cursor| cursor.seek(pos))
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.position)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.position = address;
         Ok(())
     }
+
+    fn end_instruction(&mut self) -> Result<(), Self::Error> {
+        let Some(journal) = &mut self.journal else {
+            return Ok(());
+        };
+        if journal.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut log = Vec::new();
+        for (address, bytes) in &journal.pending {
+            append_journal_entry(&mut log, *address, bytes);
+        }
+        append_journal_commit(&mut log, journal.pending.len());
+
+        journal.file.set_len(0)?;
+        journal.file.seek(SeekFrom::Start(0))?;
+        journal.file.write_all(&log)?;
+        journal.file.sync_data()?;
+
+        apply(&mut self.file, &journal.pending)?;
+        journal.pending.clear();
+
+        journal.file.set_len(0)?;
+        journal.file.sync_data()?;
+        Ok(())
+    }
+
+    fn abort_instruction(&mut self) -> Result<(), Self::Error> {
+        if let Some(journal) = &mut self.journal {
+            journal.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(contents: &[u8]) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image");
+        fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn journaled_writes_reach_the_image_at_end_of_instruction() {
+        let (_dir, path) = image(&[0x00; 4]);
+        let mut mem = FileMemory::with_journal(&path).unwrap();
+        mem.write(0x0201_u32).unwrap();
+        mem.seek(Offset(-4)).unwrap();
+
+        assert_eq!(mem.read::<u32>().unwrap(), 0x0201);
+        assert_eq!(fs::read(&path).unwrap(), vec![0x00; 4]);
+
+        mem.end_instruction().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![0x01, 0x02, 0x00, 0x00]);
+        assert!(fs::read(journal_path(&path)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn aborting_an_instruction_discards_its_journaled_writes() {
+        let (_dir, path) = image(&[0x00; 4]);
+        let mut mem = FileMemory::with_journal(&path).unwrap();
+        mem.write(0x0201_u32).unwrap();
+        mem.abort_instruction().unwrap();
+        mem.end_instruction().unwrap();
+
+        mem.seek(Offset(-4)).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0);
+        assert_eq!(fs::read(&path).unwrap(), vec![0x00; 4]);
+    }
+
+    #[test]
+    fn journaled_writes_can_extend_the_image() {
+        let (_dir, path) = image(&[0x01]);
+        let mut mem = FileMemory::with_journal(&path).unwrap();
+        mem.seek(Offset(2)).unwrap();
+        mem.write(0xFF_u8).unwrap();
+        mem.seek(Offset(-3)).unwrap();
+
+        assert_eq!(mem.read::<[u8; 3]>().unwrap(), [0x01, 0x00, 0xFF]);
+        assert_eq!(mem.read_if_present::<u8>().unwrap(), None);
+    }
+
+    #[test]
+    fn reopening_replays_a_committed_journal() {
+        let (_dir, path) = image(&[0x00; 4]);
+        let mut log = Vec::new();
+        append_journal_entry(&mut log, 1, &[0xAA, 0xBB]);
+        append_journal_entry(&mut log, 3, &[0xCC]);
+        append_journal_commit(&mut log, 2);
+        fs::write(journal_path(&path), log).unwrap();
+
+        let mut mem = FileMemory::with_path(&path).unwrap();
+        assert_eq!(mem.read::<[u8; 4]>().unwrap(), [0x00, 0xAA, 0xBB, 0xCC]);
+        assert!(!journal_path(&path).exists());
+    }
+
+    #[test]
+    fn reopening_discards_an_incomplete_journal() {
+        let (_dir, path) = image(&[0x00; 4]);
+        let mut log = Vec::new();
+        append_journal_entry(&mut log, 1, &[0xAA, 0xBB]);
+        append_journal_entry(&mut log, 3, &[0xCC]);
+        append_journal_commit(&mut log, 2);
+        log.truncate(log.len() - 1);
+        fs::write(journal_path(&path), log).unwrap();
+
+        let mut mem = FileMemory::with_path(&path).unwrap();
+        assert_eq!(mem.read::<[u8; 4]>().unwrap(), [0x00; 4]);
+        assert!(!journal_path(&path).exists());
+    }
 }
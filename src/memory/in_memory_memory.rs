@@ -1,11 +1,11 @@
 use std::{
     fs::File,
-    io::{self, Write},
+    io::{self, Seek, Write},
 };
 
 use crate::types::{Instruction, Offset, ReadWriteable};
 
-use super::Memory;
+use super::{Cursor, Memory, Storage};
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct InMemoryMemory {
@@ -21,38 +21,61 @@ impl InMemoryMemory {
     pub fn builder() -> InMemoryBuilder {
         InMemoryBuilder::new()
     }
+
+    /// Runs `f` on a cursor over the bytes at `pc`, then moves `pc` to
+    /// wherever the cursor ended up.
+    fn with_cursor<R>(&mut self, f: impl FnOnce(&mut Cursor<&mut Vec<u8>>) -> R) -> R {
+        let mut cursor = Cursor {
+            storage: &mut self.memory,
+            position: self.pc,
+        };
+        let result = f(&mut cursor);
+        self.pc = cursor.position;
+        result
+    }
+}
+
+impl Storage for InMemoryMemory {
+    type Error = ();
+
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.memory.read_at(address, buffer)
+    }
+
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.memory.write_at(address, buffer)
+    }
+
+    fn len(&self) -> Result<usize, Self::Error> {
+        Ok(self.memory.len())
+    }
 }
 
 impl Memory for InMemoryMemory {
     type Error = ();
 
     fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
-        let buffer = dbg!(&self.memory[dbg!(self.pc)..self.pc + dbg!(T::NUM_BYTES)]);
-        self.pc += T::NUM_BYTES;
-        Ok(T::from_bytes(buffer))
+        self.with_cursor(|cursor| cursor.read())
     }
 
     fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
-        if (self.pc + T::NUM_BYTES) > self.memory.len() {
-            return Ok(None);
-        }
-        let buffer = &self.memory[self.pc..self.pc + T::NUM_BYTES];
-        self.pc += T::NUM_BYTES;
-        Ok(Some(T::from_bytes(buffer)))
+        self.with_cursor(|cursor| cursor.read_if_present())
     }
 
     fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
-        if self.pc + T::NUM_BYTES > self.memory.len() {
-            self.memory.resize(self.pc + T::NUM_BYTES, 0);
-        }
-
-        let buffer = &mut self.memory[self.pc..self.pc + T::NUM_BYTES];
-        value.into_bytes(buffer);
-        Ok(())
+        self.with_cursor(|cursor| cursor.write(value))
     }
 
     fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
-        self.pc = (self.pc as i16 + pos.0) as usize;
+        self.with_cursor(|cursor| cursor.seek(pos))
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.pc)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.pc = address;
         Ok(())
     }
 }
@@ -68,7 +91,9 @@ impl InMemoryBuilder {
     }
 
     pub fn instruction<Rw: ReadWriteable>(mut self, instruction: Instruction, args: Rw) -> Self {
-        self.memory.push(instruction as u8);
+        let mut opcode = vec![0_u8; instruction.encoded_len()];
+        instruction.encode(&mut opcode);
+        self.memory.extend_from_slice(&opcode);
         let mut buffer = vec![0_u8; Rw::NUM_BYTES];
         args.into_bytes(&mut buffer);
         self.memory.extend_from_slice(&buffer);
@@ -95,6 +120,7 @@ impl InMemoryBuilder {
     pub fn to_tmp_file(self) -> Result<File, io::Error> {
         let mut file = tempfile::tempfile()?;
         file.write_all(&self.memory)?;
+        file.rewind()?;
         Ok(file)
     }
 
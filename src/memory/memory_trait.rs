@@ -1,10 +1,99 @@
-use crate::types::{Offset, ReadWriteable};
+use crate::trap::Fault;
+use crate::types::{Instruction, Offset, ReadWriteable};
 
 pub trait Memory {
-    type Error;
+    type Error: MemoryError;
 
     fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error>;
     fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error>;
     fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error>;
     fn seek(&mut self, pos: Offset) -> Result<(), Self::Error>;
+
+    /// The address of the cursor, counted in bytes from the start of memory.
+    fn position(&self) -> Result<usize, Self::Error>;
+
+    /// Moves the cursor to `address`, which may be past the end of memory.
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error>;
+
+    /// Reads the opcode of the next instruction to execute. This is a plain
+    /// `Instruction::decode` unless the backend treats instruction fetches
+    /// differently from data reads (see `ProtectedMemory`).
+    fn fetch(&mut self) -> Result<Option<Instruction>, Self::Error> {
+        Instruction::decode(self)
+    }
+
+    /// Called by the machine after every instruction has finished executing.
+    /// Backends that batch up writes (e.g. a journaled `FileMemory`) can use
+    /// it to commit them.
+    fn end_instruction(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called by the machine instead of `end_instruction` when an instruction
+    /// faults. Backends that batch up writes should throw them away, so that
+    /// the faulting instruction's writes never reach storage. Writes that were
+    /// made straight away can't be undone.
+    fn abort_instruction(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Reads the value at the cursor and, if `update` returns a new value,
+    /// writes it back. Either way the old value is returned, and the cursor
+    /// ends up after the value.
+    ///
+    /// Instructions never interleave, so this is atomic as far as a
+    /// `Scheduler` is concerned. Backends that are shared between OS threads
+    /// (see `SharedMemory`) override it with real atomics.
+    fn atomic_update<T: AtomicValue>(
+        &mut self,
+        mut update: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, Self::Error> {
+        let current = self.read::<T>()?;
+        if let Some(new) = update(current) {
+            self.seek(Offset(-(T::NUM_BYTES as i16)))?;
+            self.write(new)?;
+        }
+        Ok(current)
+    }
+}
+
+/// Values that can be updated with `Memory::atomic_update`.
+pub trait AtomicValue: ReadWriteable + Copy + PartialEq {
+    fn to_u64(self) -> u64;
+    fn from_u64(value: u64) -> Self;
+}
+
+impl AtomicValue for u32 {
+    fn to_u64(self) -> u64 {
+        self as u64
+    }
+
+    fn from_u64(value: u64) -> Self {
+        value as u32
+    }
+}
+
+impl AtomicValue for u64 {
+    fn to_u64(self) -> u64 {
+        self
+    }
+
+    fn from_u64(value: u64) -> Self {
+        value
+    }
 }
+
+/// Errors produced by a `Memory` backend.
+///
+/// Most backend errors are fatal (a file couldn't be read, etc), but some
+/// backends can also refuse an access on behalf of the program. Those are
+/// reported through `fault`, so that the machine can raise them as traps.
+pub trait MemoryError {
+    fn fault(&self) -> Option<Fault> {
+        None
+    }
+}
+
+impl MemoryError for () {}
+
+impl MemoryError for std::io::Error {}
@@ -1,7 +1,21 @@
+mod cached_memory;
+mod device_memory;
 mod file_memory;
 mod in_memory_memory;
 mod memory_trait;
+mod overlay_memory;
+mod paged_memory;
+mod protected_memory;
+mod shared_memory;
+mod storage;
 
+pub use cached_memory::CachedMemory;
+pub use device_memory::{Clock, Console, Device, DeviceError, DeviceMemory, Keyboard};
 pub use file_memory::FileMemory;
 pub use in_memory_memory::{InMemoryBuilder, InMemoryMemory};
-pub use memory_trait::Memory;
+pub use memory_trait::{AtomicValue, Memory, MemoryError};
+pub use overlay_memory::{Change, OverlayMemory};
+pub use paged_memory::{PagedMemory, PAGE_SIZE};
+pub use protected_memory::{Access, ProtectedMemory, Protection, ProtectionError};
+pub use shared_memory::{SharedMemory, SharedMemoryError};
+pub use storage::{Cursor, Storage};
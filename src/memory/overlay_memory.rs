@@ -0,0 +1,227 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Result as IoResult, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::types::{Offset, ReadWriteable};
+
+use super::Memory;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub address: usize,
+    pub before: u8,
+    pub after: u8,
+}
+
+/// Runs on top of a base image without modifying it. Reads come from the
+/// base file unless the address has been written to, in which case they come
+/// from the in-memory delta. Nothing touches the file until `commit`.
+#[derive(Debug)]
+pub struct OverlayMemory {
+    pub pc: usize,
+    base: File,
+    base_len: usize,
+    delta: BTreeMap<usize, u8>,
+}
+
+impl OverlayMemory {
+    /// Opens the image read-only. Use `with_file` with a writable file if
+    /// the delta should be committable.
+    pub fn with_path(file: impl AsRef<Path>) -> IoResult<Self> {
+        Self::with_file(File::open(file.as_ref())?)
+    }
+
+    pub fn with_file(file: File) -> IoResult<Self> {
+        Ok(OverlayMemory {
+            pc: 0,
+            base_len: file.metadata()?.len() as usize,
+            base: file,
+            delta: BTreeMap::new(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        let written = self.delta.keys().next_back().map_or(0, |&last| last + 1);
+        self.base_len.max(written)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every byte whose value currently differs from the base image.
+    pub fn diff(&mut self) -> IoResult<Vec<Change>> {
+        let mut changes = Vec::new();
+        for (&address, &after) in &self.delta {
+            let mut before = [0_u8; 1];
+            read_base(&mut self.base, self.base_len, address, &mut before)?;
+            if before[0] != after {
+                changes.push(Change {
+                    address,
+                    before: before[0],
+                    after,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    pub fn discard(&mut self) {
+        self.delta.clear();
+    }
+
+    /// Writes the delta back to the base file. The file must have been
+    /// opened for writing.
+    pub fn commit(&mut self) -> IoResult<()> {
+        for (&address, &byte) in &self.delta {
+            self.base.seek(SeekFrom::Start(address as u64))?;
+            self.base.write_all(&[byte])?;
+        }
+        self.base.flush()?;
+        self.base_len = self.len();
+        self.delta.clear();
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> IoResult<()> {
+        read_base(&mut self.base, self.base_len, self.pc, buffer)?;
+        for (&address, &byte) in self.delta.range(self.pc..self.pc + buffer.len()) {
+            buffer[address - self.pc] = byte;
+        }
+        Ok(())
+    }
+}
+
+/// Reads from the base file, treating anything past its end as zero.
+fn read_base(base: &mut File, base_len: usize, address: usize, buffer: &mut [u8]) -> IoResult<()> {
+    buffer.fill(0);
+    if address < base_len {
+        let available = buffer.len().min(base_len - address);
+        base.seek(SeekFrom::Start(address as u64))?;
+        base.read_exact(&mut buffer[0..available])?;
+    }
+    Ok(())
+}
+
+impl Memory for OverlayMemory {
+    type Error = std::io::Error;
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        let mut buffer = [0_u8; 8];
+        self.read_bytes(&mut buffer[0..T::NUM_BYTES])?;
+        self.pc += T::NUM_BYTES;
+        Ok(T::from_bytes(&buffer[0..T::NUM_BYTES]))
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        if (self.pc + T::NUM_BYTES) > self.len() {
+            return Ok(None);
+        }
+        self.read().map(Some)
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        let mut buffer = [0_u8; 8];
+        value.into_bytes(&mut buffer[0..T::NUM_BYTES]);
+        for (index, &byte) in buffer[0..T::NUM_BYTES].iter().enumerate() {
+            self.delta.insert(self.pc + index, byte);
+        }
+        self.pc += T::NUM_BYTES;
+        Ok(())
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.pc = self.pc.wrapping_add_signed(pos.0 as isize);
+        Ok(())
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.pc)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.pc = address;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::InMemoryMemory;
+
+    use super::*;
+
+    fn overlay(bytes: &[u8]) -> OverlayMemory {
+        let file = InMemoryMemory::builder()
+            .bytes(bytes)
+            .to_tmp_file()
+            .unwrap();
+        OverlayMemory::with_file(file).unwrap()
+    }
+
+    fn base_contents(mem: &mut OverlayMemory) -> Vec<u8> {
+        let mut contents = Vec::new();
+        mem.base.rewind().unwrap();
+        mem.base.read_to_end(&mut contents).unwrap();
+        contents
+    }
+
+    #[test]
+    fn writes_do_not_modify_the_base_image() {
+        let mut mem = overlay(&[0x01, 0x02, 0x03]);
+        mem.seek(Offset(1)).unwrap();
+        mem.write(0xFF_u8).unwrap();
+        mem.seek(Offset(-2)).unwrap();
+
+        assert_eq!(mem.read::<[u8; 3]>().unwrap(), [0x01, 0xFF, 0x03]);
+        assert_eq!(base_contents(&mut mem), vec![0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn diff_reports_changed_bytes_only() {
+        let mut mem = overlay(&[0x01, 0x02]);
+        mem.write([0x01_u8, 0x22]).unwrap();
+        mem.write(0x33_u8).unwrap();
+
+        assert_eq!(
+            mem.diff().unwrap(),
+            vec![
+                Change {
+                    address: 1,
+                    before: 0x02,
+                    after: 0x22
+                },
+                Change {
+                    address: 2,
+                    before: 0x00,
+                    after: 0x33
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn discarding_restores_the_base_image() {
+        let mut mem = overlay(&[0x01, 0x02]);
+        mem.write(0xFFFF_u32).unwrap();
+        mem.discard();
+        mem.seek(Offset(-4)).unwrap();
+
+        assert_eq!(mem.len(), 2);
+        assert_eq!(mem.read::<[u8; 2]>().unwrap(), [0x01, 0x02]);
+    }
+
+    #[test]
+    fn committing_writes_the_delta_to_the_base_image() {
+        let mut mem = overlay(&[0x01, 0x02]);
+        mem.seek(Offset(1)).unwrap();
+        mem.write([0xAA_u8, 0xBB]).unwrap();
+        mem.commit().unwrap();
+
+        assert!(mem.diff().unwrap().is_empty());
+        assert_eq!(base_contents(&mut mem), vec![0x01, 0xAA, 0xBB]);
+    }
+}
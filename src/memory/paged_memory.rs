@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use crate::types::{Offset, ReadWriteable};
+
+use super::Memory;
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// A sparse memory that only allocates pages once they are written to.
+/// Unallocated pages read as zero.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PagedMemory {
+    pub pc: usize,
+    len: usize,
+    pages: BTreeMap<usize, Box<[u8; PAGE_SIZE]>>,
+}
+
+impl PagedMemory {
+    pub fn from_vec(memory: Vec<u8>) -> Self {
+        let mut paged = PagedMemory {
+            pc: 0,
+            len: memory.len(),
+            pages: BTreeMap::new(),
+        };
+        for (index, chunk) in memory.chunks(PAGE_SIZE).enumerate() {
+            if chunk.iter().any(|&byte| byte != 0) {
+                paged.write_bytes(index * PAGE_SIZE, chunk);
+            }
+        }
+        paged
+    }
+
+    /// The size of the memory, i.e. one past the highest address that was
+    /// either part of the initial image or has been written to since.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The indexes of all pages that have been allocated. Page `n` covers
+    /// addresses `n * PAGE_SIZE..(n + 1) * PAGE_SIZE`.
+    pub fn touched_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.pages.keys().copied()
+    }
+
+    fn read_bytes(&self, address: usize, buffer: &mut [u8]) {
+        for (index, byte) in buffer.iter_mut().enumerate() {
+            let address = address + index;
+            *byte = match self.pages.get(&(address / PAGE_SIZE)) {
+                Some(page) => page[address % PAGE_SIZE],
+                None => 0,
+            };
+        }
+    }
+
+    fn write_bytes(&mut self, address: usize, buffer: &[u8]) {
+        for (index, &byte) in buffer.iter().enumerate() {
+            let address = address + index;
+            let page = self
+                .pages
+                .entry(address / PAGE_SIZE)
+                .or_insert_with(|| Box::new([0; PAGE_SIZE]));
+            page[address % PAGE_SIZE] = byte;
+        }
+        self.len = self.len.max(address + buffer.len());
+    }
+}
+
+impl Memory for PagedMemory {
+    type Error = ();
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        let mut buffer = [0_u8; 8];
+        self.read_bytes(self.pc, &mut buffer[0..T::NUM_BYTES]);
+        self.pc += T::NUM_BYTES;
+        Ok(T::from_bytes(&buffer[0..T::NUM_BYTES]))
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        if (self.pc + T::NUM_BYTES) > self.len {
+            return Ok(None);
+        }
+        self.read().map(Some)
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        let mut buffer = [0_u8; 8];
+        value.into_bytes(&mut buffer[0..T::NUM_BYTES]);
+        self.write_bytes(self.pc, &buffer[0..T::NUM_BYTES]);
+        self.pc += T::NUM_BYTES;
+        Ok(())
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.pc = self.pc.wrapping_add_signed(pos.0 as isize);
+        Ok(())
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.pc)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.pc = address;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_away_writes_only_allocate_a_single_page() {
+        let mut mem = PagedMemory::default();
+        for _ in 0..4 {
+            mem.seek(Offset(i16::MAX)).unwrap();
+        }
+        mem.write(0xFF_u8).unwrap();
+
+        assert_eq!(mem.len(), 4 * i16::MAX as usize + 1);
+        assert_eq!(
+            mem.touched_pages().collect::<Vec<_>>(),
+            vec![4 * i16::MAX as usize / PAGE_SIZE]
+        );
+    }
+
+    #[test]
+    fn unallocated_pages_read_as_zero() {
+        let mut mem = PagedMemory::from_vec(vec![0x00; 3 * PAGE_SIZE]);
+        assert_eq!(mem.touched_pages().count(), 0);
+        mem.seek(Offset(PAGE_SIZE as i16)).unwrap();
+        assert_eq!(mem.read::<u64>().unwrap(), 0);
+        assert_eq!(mem.touched_pages().count(), 0);
+    }
+
+    #[test]
+    fn accesses_can_cross_page_boundaries() {
+        let mut mem = PagedMemory::default();
+        mem.seek(Offset(PAGE_SIZE as i16 - 2)).unwrap();
+        mem.write(0x04030201_u32).unwrap();
+        mem.seek(Offset(-4)).unwrap();
+
+        assert_eq!(mem.read::<u32>().unwrap(), 0x04030201);
+        assert_eq!(mem.touched_pages().collect::<Vec<_>>(), vec![0, 1]);
+    }
+}
@@ -0,0 +1,279 @@
+use std::ops::Range;
+
+use crate::trap::Fault;
+use crate::types::{Instruction, Offset, ReadWriteable};
+
+use super::{AtomicValue, Memory, MemoryError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Protection {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Protection {
+    pub const ALL: Protection = Protection {
+        read: true,
+        write: true,
+        execute: true,
+    };
+    /// Can be read and executed, but never written (e.g. a bootstrap region).
+    pub const READ_ONLY: Protection = Protection {
+        read: true,
+        write: false,
+        execute: true,
+    };
+    pub const NO_EXECUTE: Protection = Protection {
+        read: true,
+        write: true,
+        execute: false,
+    };
+    pub const WRITE_ONLY: Protection = Protection {
+        read: false,
+        write: true,
+        execute: false,
+    };
+
+    pub fn allows(self, access: Access) -> bool {
+        match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProtectionError<E> {
+    Fault { address: usize, access: Access },
+    Memory(E),
+}
+
+impl<E: MemoryError> MemoryError for ProtectionError<E> {
+    fn fault(&self) -> Option<Fault> {
+        match self {
+            ProtectionError::Fault { address, access } => Some(Fault::Protection {
+                address: *address,
+                access: *access,
+            }),
+            ProtectionError::Memory(err) => err.fault(),
+        }
+    }
+}
+
+/// Wraps another memory and refuses accesses to regions that don't allow
+/// them.
+///
+/// Regions are given in the wrapped memory's addresses. If regions overlap,
+/// an access must be allowed by all of them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProtectedMemory<Mem: Memory> {
+    pub memory: Mem,
+    regions: Vec<(Range<usize>, Protection)>,
+}
+
+impl<Mem: Memory> ProtectedMemory<Mem> {
+    pub fn with_memory(memory: Mem) -> Self {
+        ProtectedMemory {
+            memory,
+            regions: Vec::new(),
+        }
+    }
+
+    pub fn protect(mut self, range: Range<usize>, protection: Protection) -> Self {
+        self.regions.push((range, protection));
+        self
+    }
+
+    fn check(&self, len: usize, access: Access) -> Result<(), ProtectionError<Mem::Error>> {
+        let position = self.memory.position().map_err(ProtectionError::Memory)?;
+        self.check_at(position, len, access)
+    }
+
+    fn check_at(
+        &self,
+        address: usize,
+        len: usize,
+        access: Access,
+    ) -> Result<(), ProtectionError<Mem::Error>> {
+        let accessed = address..address + len;
+        let offending = self
+            .regions
+            .iter()
+            .filter(|(range, protection)| {
+                range.start < accessed.end
+                    && accessed.start < range.end
+                    && !protection.allows(access)
+            })
+            .map(|(range, _)| range.start.max(accessed.start))
+            .min();
+
+        match offending {
+            Some(address) => Err(ProtectionError::Fault { address, access }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<Mem: Memory> Memory for ProtectedMemory<Mem> {
+    type Error = ProtectionError<Mem::Error>;
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        self.check(T::NUM_BYTES, Access::Read)?;
+        self.memory.read().map_err(ProtectionError::Memory)
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        self.check(T::NUM_BYTES, Access::Read)?;
+        self.memory
+            .read_if_present()
+            .map_err(ProtectionError::Memory)
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        self.check(T::NUM_BYTES, Access::Write)?;
+        self.memory.write(value).map_err(ProtectionError::Memory)
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.memory.seek(pos).map_err(ProtectionError::Memory)
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        self.memory.position().map_err(ProtectionError::Memory)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.memory
+            .set_position(address)
+            .map_err(ProtectionError::Memory)
+    }
+
+    fn end_instruction(&mut self) -> Result<(), Self::Error> {
+        self.memory
+            .end_instruction()
+            .map_err(ProtectionError::Memory)
+    }
+
+    fn abort_instruction(&mut self) -> Result<(), Self::Error> {
+        self.memory
+            .abort_instruction()
+            .map_err(ProtectionError::Memory)
+    }
+
+    fn atomic_update<T: AtomicValue>(
+        &mut self,
+        update: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, Self::Error> {
+        self.check(T::NUM_BYTES, Access::Read)?;
+        self.check(T::NUM_BYTES, Access::Write)?;
+        self.memory
+            .atomic_update(update)
+            .map_err(ProtectionError::Memory)
+    }
+
+    fn fetch(&mut self) -> Result<Option<Instruction>, Self::Error> {
+        let start = self.memory.position().map_err(ProtectionError::Memory)?;
+        self.check_at(start, 1, Access::Execute)?;
+        let instruction = self.memory.fetch().map_err(ProtectionError::Memory)?;
+        if let Some(instruction) = instruction {
+            // Extended opcodes are two bytes, and both must be executable
+            self.check_at(start, instruction.encoded_len(), Access::Execute)?;
+        }
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::InMemoryMemory;
+
+    use super::*;
+
+    fn protected(memory: Vec<u8>) -> ProtectedMemory<InMemoryMemory> {
+        ProtectedMemory::with_memory(InMemoryMemory::from_vec(memory))
+    }
+
+    #[test]
+    fn unprotected_accesses_pass_through() {
+        let mut mem = protected(vec![0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(mem.read::<[u8; 2]>().unwrap(), [0x01, 0x02]);
+        mem.write(0xFF_u8).unwrap();
+        assert_eq!(mem.memory.memory, vec![0x01, 0x02, 0xFF, 0x04]);
+    }
+
+    #[test]
+    fn writing_to_read_only_region_faults() {
+        let mut mem = protected(vec![0x00; 8]).protect(2..4, Protection::READ_ONLY);
+        mem.seek(Offset(1)).unwrap();
+
+        let err = mem.write(0xFFFF_u32).unwrap_err();
+        assert_eq!(
+            err.fault(),
+            Some(Fault::Protection {
+                address: 2,
+                access: Access::Write
+            })
+        );
+        assert_eq!(mem.memory.memory, vec![0x00; 8]);
+    }
+
+    #[test]
+    fn reading_from_read_only_region_is_allowed() {
+        let mut mem = protected(vec![0x00, 0x01, 0x02]).protect(0..3, Protection::READ_ONLY);
+        assert_eq!(mem.read::<[u8; 3]>().unwrap(), [0x00, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn fetching_from_no_execute_region_faults() {
+        let mut mem = protected(vec![0x00, 0x00]).protect(1..2, Protection::NO_EXECUTE);
+        assert_eq!(mem.fetch().unwrap(), Some(Instruction::Noop));
+        assert_eq!(
+            mem.fetch().unwrap_err().fault(),
+            Some(Fault::Protection {
+                address: 1,
+                access: Access::Execute
+            })
+        );
+    }
+
+    #[test]
+    fn reading_from_write_only_region_faults() {
+        let mut mem = protected(vec![0x00; 4]).protect(0..4, Protection::WRITE_ONLY);
+        mem.write(0x01_u8).unwrap();
+        mem.seek(Offset(-1)).unwrap();
+        assert_eq!(
+            mem.read::<u8>().unwrap_err().fault(),
+            Some(Fault::Protection {
+                address: 0,
+                access: Access::Read
+            })
+        );
+    }
+
+    #[test]
+    fn overlapping_regions_must_all_allow_access() {
+        let mut mem = protected(vec![0x00; 4])
+            .protect(0..4, Protection::READ_ONLY)
+            .protect(0..4, Protection::NO_EXECUTE);
+        assert!(mem.read::<u8>().is_ok());
+        assert!(mem.fetch().is_err());
+    }
+
+    #[test]
+    fn regions_use_the_wrapped_memorys_addresses() {
+        let mut inner = InMemoryMemory::from_vec(vec![0x00; 4]);
+        inner.pc = 2;
+        let mut mem = ProtectedMemory::with_memory(inner).protect(2..3, Protection::READ_ONLY);
+        assert_eq!(mem.position().unwrap(), 2);
+        assert!(mem.write(0x01_u8).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::trap::Fault;
+use crate::types::{Offset, ReadWriteable};
+
+use super::{AtomicValue, Cursor, Memory, MemoryError, Storage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedMemoryError {
+    /// The access went past the end of the memory, which can't grow.
+    OutOfBounds {
+        address: usize,
+    },
+    Misaligned {
+        address: usize,
+    },
+}
+
+impl MemoryError for SharedMemoryError {
+    fn fault(&self) -> Option<Fault> {
+        match self {
+            SharedMemoryError::OutOfBounds { .. } => None,
+            SharedMemoryError::Misaligned { address } => {
+                Some(Fault::MisalignedAtomic { address: *address })
+            }
+        }
+    }
+}
+
+/// A fixed-size memory that can be shared between machines running on
+/// different OS threads. Cloning it gives another handle to the same bytes,
+/// with its own cursor.
+///
+/// It's also a `Storage`, so each thread of a `Scheduler` can have a cursor
+/// over it.
+///
+/// Plain reads and writes are only atomic byte by byte, but
+/// `atomic_update` maps onto real atomics. It requires the value to be
+/// aligned to its size.
+#[derive(Debug, Clone)]
+pub struct SharedMemory {
+    pub pc: usize,
+    len: usize,
+    words: Arc<[AtomicU64]>,
+}
+
+impl SharedMemory {
+    pub fn from_vec(memory: Vec<u8>) -> Self {
+        let words = memory
+            .chunks(8)
+            .map(|chunk| {
+                let mut word = [0_u8; 8];
+                word[0..chunk.len()].copy_from_slice(chunk);
+                AtomicU64::new(u64::from_le_bytes(word))
+            })
+            .collect();
+        SharedMemory {
+            pc: 0,
+            len: memory.len(),
+            words,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies out the current contents.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = self
+            .words
+            .iter()
+            .flat_map(|word| word.load(Ordering::SeqCst).to_le_bytes())
+            .collect::<Vec<_>>();
+        bytes.truncate(self.len);
+        bytes
+    }
+
+    fn check_bounds(&self, address: usize, len: usize) -> Result<(), SharedMemoryError> {
+        match address + len <= self.len {
+            true => Ok(()),
+            false => Err(SharedMemoryError::OutOfBounds { address }),
+        }
+    }
+
+    /// Runs `f` on a cursor over the bytes at `pc`, then moves `pc` to
+    /// wherever the cursor ended up.
+    fn with_cursor<R>(&mut self, f: impl FnOnce(&mut Cursor<&mut Self>) -> R) -> R {
+        let mut cursor = Cursor {
+            position: self.pc,
+            storage: &mut *self,
+        };
+        let result = f(&mut cursor);
+        self.pc = cursor.position;
+        result
+    }
+
+    /// Atomically replaces the `mask` bits of a word at `shift`.
+    fn update_bits(
+        &self,
+        address: usize,
+        mask: u64,
+        mut update: impl FnMut(u64) -> Option<u64>,
+    ) -> u64 {
+        let shift = (address % 8) * 8;
+        let mut old = 0;
+        let _ = self.words[address / 8].fetch_update(Ordering::SeqCst, Ordering::SeqCst, |word| {
+            old = (word >> shift) & mask;
+            update(old).map(|new| (word & !(mask << shift)) | ((new & mask) << shift))
+        });
+        old
+    }
+}
+
+impl Storage for SharedMemory {
+    type Error = SharedMemoryError;
+
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_bounds(address, buffer.len())?;
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            let word = self.words[address / 8].load(Ordering::SeqCst);
+            *byte = (word >> ((address % 8) * 8)) as u8;
+        }
+        Ok(())
+    }
+
+    /// Fails rather than growing the memory, which is a fixed size.
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.check_bounds(address, buffer.len())?;
+        for (address, &byte) in (address..).zip(buffer) {
+            self.update_bits(address, 0xFF, |_| Some(byte as u64));
+        }
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize, Self::Error> {
+        Ok(self.len)
+    }
+}
+
+impl Memory for SharedMemory {
+    type Error = SharedMemoryError;
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        self.with_cursor(|cursor| cursor.read())
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        self.with_cursor(|cursor| cursor.read_if_present())
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        self.with_cursor(|cursor| cursor.write(value))
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.with_cursor(|cursor| cursor.seek(pos))
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.pc)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.pc = address;
+        Ok(())
+    }
+
+    fn atomic_update<T: AtomicValue>(
+        &mut self,
+        mut update: impl FnMut(T) -> Option<T>,
+    ) -> Result<T, Self::Error> {
+        self.check_bounds(self.pc, T::NUM_BYTES)?;
+        if !self.pc.is_multiple_of(T::NUM_BYTES) {
+            return Err(SharedMemoryError::Misaligned { address: self.pc });
+        }
+        let mask = u64::MAX >> (64 - 8 * T::NUM_BYTES);
+        let old = self.update_bits(self.pc, mask, |old| update(T::from_u64(old)).map(T::to_u64));
+        self.pc += T::NUM_BYTES;
+        Ok(T::from_u64(old))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn clones_share_bytes_but_not_cursors() {
+        let mut first = SharedMemory::from_vec(vec![0x00; 10]);
+        let mut second = first.clone();
+        first.seek(Offset(9)).unwrap();
+        first.write(0xAB_u8).unwrap();
+
+        assert_eq!(second.position().unwrap(), 0);
+        second.seek(Offset(8)).unwrap();
+        assert_eq!(second.read::<[u8; 2]>().unwrap(), [0x00, 0xAB]);
+        assert!(second.write(0x00_u8).is_err());
+    }
+
+    #[test]
+    fn cursors_can_share_it_as_storage() {
+        let memory = SharedMemory::from_vec(vec![0x00; 4]);
+        let mut cursor = Cursor::new(memory.clone());
+        cursor.seek(Offset(2)).unwrap();
+        cursor.write(0xABCD_u16).unwrap();
+
+        assert_eq!(memory.to_vec(), [0x00, 0x00, 0xCD, 0xAB]);
+        assert!(cursor.write(0x00_u8).is_err());
+    }
+
+    #[test]
+    fn fetch_add_is_atomic_across_threads() {
+        let memory = SharedMemory::from_vec(vec![0x00; 16]);
+        let threads = (0..4)
+            .map(|_| {
+                let mut memory = memory.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        memory.set_position(4).unwrap();
+                        memory.atomic_update(|value: u32| Some(value + 1)).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(memory.to_vec()[4..8], 4000_u32.to_le_bytes());
+        assert_eq!(memory.to_vec()[0..4], [0x00; 4]);
+    }
+
+    #[test]
+    fn misaligned_atomics_fault() {
+        let mut memory = SharedMemory::from_vec(vec![0x00; 16]);
+        memory.set_position(2).unwrap();
+        assert_eq!(
+            memory
+                .atomic_update(|value: u64| Some(value))
+                .unwrap_err()
+                .fault(),
+            Some(Fault::MisalignedAtomic { address: 2 })
+        );
+    }
+}
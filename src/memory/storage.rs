@@ -0,0 +1,178 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::types::{Offset, ReadWriteable};
+
+use super::{Memory, MemoryError};
+
+/// Random-access bytes, without a cursor. `Cursor` turns a storage into a
+/// `Memory`, and several cursors can take turns over the same storage.
+pub trait Storage {
+    type Error: MemoryError;
+
+    /// Fills `buffer` with the bytes starting at `address`.
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Writes `buffer` starting at `address`, growing the storage if needed.
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error>;
+
+    fn len(&self) -> Result<usize, Self::Error>;
+
+    fn is_empty(&self) -> Result<bool, Self::Error> {
+        Ok(self.len()? == 0)
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for &mut S {
+    type Error = S::Error;
+
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        (**self).read_at(address, buffer)
+    }
+
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error> {
+        (**self).write_at(address, buffer)
+    }
+
+    fn len(&self) -> Result<usize, Self::Error> {
+        (**self).len()
+    }
+}
+
+/// Lets several cursors own the same storage, e.g. one for each thread of a
+/// `Scheduler`.
+impl<S: Storage> Storage for Rc<RefCell<S>> {
+    type Error = S::Error;
+
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.borrow_mut().read_at(address, buffer)
+    }
+
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error> {
+        self.borrow_mut().write_at(address, buffer)
+    }
+
+    fn len(&self) -> Result<usize, Self::Error> {
+        self.borrow().len()
+    }
+}
+
+impl Storage for Vec<u8> {
+    type Error = ();
+
+    fn read_at(&mut self, address: usize, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let bytes = self.get(address..address + buffer.len()).ok_or(())?;
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_at(&mut self, address: usize, buffer: &[u8]) -> Result<(), Self::Error> {
+        if address + buffer.len() > Vec::len(self) {
+            self.resize(address + buffer.len(), 0);
+        }
+        self[address..address + buffer.len()].copy_from_slice(buffer);
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize, Self::Error> {
+        Ok(Vec::len(self))
+    }
+}
+
+/// A cursor over a `Storage`, giving it the `Memory` interface.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Cursor<S: Storage> {
+    pub storage: S,
+    pub position: usize,
+}
+
+impl<S: Storage> Cursor<S> {
+    pub fn new(storage: S) -> Self {
+        Cursor {
+            storage,
+            position: 0,
+        }
+    }
+}
+
+impl<S: Storage> Memory for Cursor<S> {
+    type Error = S::Error;
+
+    fn read<T: ReadWriteable>(&mut self) -> Result<T, Self::Error> {
+        let value = read_value(&mut self.storage, self.position)?;
+        self.position += T::NUM_BYTES;
+        Ok(value)
+    }
+
+    fn read_if_present<T: ReadWriteable>(&mut self) -> Result<Option<T>, Self::Error> {
+        if self.position + T::NUM_BYTES > self.storage.len()? {
+            return Ok(None);
+        }
+        self.read().map(Some)
+    }
+
+    fn write<T: ReadWriteable>(&mut self, value: T) -> Result<(), Self::Error> {
+        write_value(&mut self.storage, self.position, value)?;
+        self.position += T::NUM_BYTES;
+        Ok(())
+    }
+
+    fn seek(&mut self, pos: Offset) -> Result<(), Self::Error> {
+        self.position = self.position.wrapping_add_signed(pos.0 as isize);
+        Ok(())
+    }
+
+    fn position(&self) -> Result<usize, Self::Error> {
+        Ok(self.position)
+    }
+
+    fn set_position(&mut self, address: usize) -> Result<(), Self::Error> {
+        self.position = address;
+        Ok(())
+    }
+}
+
+fn read_value<S: Storage + ?Sized, T: ReadWriteable>(
+    storage: &mut S,
+    address: usize,
+) -> Result<T, S::Error> {
+    let mut buffer = [0_u8; 8];
+    storage.read_at(address, &mut buffer[0..T::NUM_BYTES])?;
+    Ok(T::from_bytes(&buffer[0..T::NUM_BYTES]))
+}
+
+fn write_value<S: Storage + ?Sized, T: ReadWriteable>(
+    storage: &mut S,
+    address: usize,
+    value: T,
+) -> Result<(), S::Error> {
+    let mut buffer = [0_u8; 8];
+    value.into_bytes(&mut buffer[0..T::NUM_BYTES]);
+    storage.write_at(address, &buffer[0..T::NUM_BYTES])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_over_the_same_storage_move_independently() {
+        let mut storage = vec![0x01, 0x02, 0x03, 0x04];
+        let mut first = Cursor::new(&mut storage);
+        first.seek(Offset(2)).unwrap();
+        first.write(0xFF_u8).unwrap();
+        assert_eq!(first.position().unwrap(), 3);
+
+        let mut second = Cursor::new(&mut storage);
+        assert_eq!(second.read::<[u8; 4]>().unwrap(), [0x01, 0x02, 0xFF, 0x04]);
+    }
+
+    #[test]
+    fn storage_can_be_read_without_moving_anything() {
+        let mut storage = vec![0x01, 0x02, 0x03];
+        let mut buffer = [0; 2];
+        storage.read_at(1, &mut buffer).unwrap();
+        assert_eq!(buffer, [0x02, 0x03]);
+        assert!(storage.read_at(2, &mut buffer).is_err());
+    }
+}
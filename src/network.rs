@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::machine::Machine;
+use crate::memory::Memory;
+use crate::trap::{Fault, Trap};
+
+/// A queue of messages between machines. Cloning it gives another handle to
+/// the same queue, so one end can be given to the sender and the other to
+/// the receiver, as a `Port`.
+#[derive(Debug, Clone, Default)]
+pub struct Channel {
+    messages: Rc<RefCell<VecDeque<Vec<u8>>>>,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Channel::default()
+    }
+
+    /// Queues a message. Programs see its length as a `u16`, so it can't be
+    /// any longer than that.
+    ///
+    /// # Panics
+    ///
+    /// If the message is longer than `u16::MAX` bytes.
+    pub fn send(&self, message: Vec<u8>) {
+        assert!(message.len() <= u16::MAX as usize, "message too long");
+        self.messages.borrow_mut().push_back(message);
+    }
+
+    /// Takes the oldest message, if there is one.
+    pub fn try_recv(&self) -> Option<Vec<u8>> {
+        self.messages.borrow_mut().pop_front()
+    }
+
+    /// The number of messages waiting to be received.
+    pub fn len(&self) -> usize {
+        self.messages.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.borrow().is_empty()
+    }
+}
+
+/// Two handles are equal if they're for the same queue.
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.messages, &other.messages)
+    }
+}
+
+/// A machine's end of a channel. Messages only go one way, so a machine can
+/// never receive what it sent itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Port {
+    Sender(Channel),
+    Receiver(Channel),
+}
+
+impl Port {
+    pub fn sender(&self) -> Option<&Channel> {
+        match self {
+            Port::Sender(channel) => Some(channel),
+            Port::Receiver(_) => None,
+        }
+    }
+
+    pub fn receiver(&self) -> Option<&Channel> {
+        match self {
+            Port::Sender(_) => None,
+            Port::Receiver(channel) => Some(channel),
+        }
+    }
+}
+
+/// Runs a graph of machines connected by channels, on the current thread.
+///
+/// Machines take turns in the order they were added. Each runs for up to
+/// `quantum` instructions, or until it has to wait for a message, so the
+/// result only depends on the programs. The network stops once every machine
+/// has run off the end of its memory.
+#[derive(Debug)]
+pub struct Network<Mem: Memory> {
+    pub machines: Vec<Machine<Mem>>,
+    finished: Vec<bool>,
+    current: usize,
+    /// Where `pick` starts looking for a machine to run.
+    turn: usize,
+    quantum: u32,
+}
+
+impl<Mem: Memory> Default for Network<Mem> {
+    fn default() -> Self {
+        Network {
+            machines: Vec::new(),
+            finished: Vec::new(),
+            current: 0,
+            turn: 0,
+            quantum: 100,
+        }
+    }
+}
+
+impl<Mem: Memory> Network<Mem> {
+    pub fn new() -> Self {
+        Network::default()
+    }
+
+    pub fn quantum(mut self, quantum: u32) -> Self {
+        self.quantum = quantum;
+        self
+    }
+
+    /// Adds a machine to the network, returning its index.
+    pub fn add(&mut self, machine: Machine<Mem>) -> usize {
+        self.machines.push(machine);
+        self.finished.push(false);
+        self.machines.len() - 1
+    }
+
+    /// Creates a channel from port `from.1` of machine `from.0` to port
+    /// `to.1` of machine `to.0`, replacing whatever those ports were
+    /// connected to. The first can only send, and the second only receive.
+    pub fn connect(&mut self, from: (usize, u8), to: (usize, u8)) -> Channel {
+        let channel = Channel::new();
+        self.machines[from.0]
+            .cpu
+            .ports
+            .insert(from.1, Port::Sender(channel.clone()));
+        self.machines[to.0]
+            .cpu
+            .ports
+            .insert(to.1, Port::Receiver(channel.clone()));
+        channel
+    }
+
+    /// The index of the machine that ran (or is about to run) the latest
+    /// instruction.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn run(&mut self) -> Result<(), Trap<Mem::Error>> {
+        while let Some(next) = self.pick()? {
+            self.current = next;
+            self.turn = next + 1;
+            let machine = &mut self.machines[next];
+            for _ in 0..self.quantum.max(1) {
+                if !machine.step()? {
+                    self.finished[next] = true;
+                    break;
+                }
+                if machine.cpu.waiting() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Chooses the machine to run next, going round in order, or `None` if
+    /// every machine has finished.
+    fn pick(&self) -> Result<Option<usize>, Fault> {
+        let count = self.machines.len();
+        let mut unfinished = false;
+        for index in (0..count).map(|step| (self.turn + step) % count) {
+            if self.finished[index] {
+                continue;
+            }
+            unfinished = true;
+            if !self.machines[index].cpu.waiting() {
+                return Ok(Some(index));
+            }
+        }
+        match unfinished {
+            true => Err(Fault::Deadlock),
+            false => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::InMemoryMemory;
+    use crate::types::{Instruction, Offset, OffsetPair};
+
+    use super::*;
+
+    fn producer() -> Machine<InMemoryMemory> {
+        // Sends its last two bytes, then the last one on its own
+        Machine::with_memory(
+            InMemoryMemory::builder()
+                .instruction(Instruction::Send, (0_u8, 2_u16, Offset(10)))
+                .instruction(Instruction::Send, (0_u8, 1_u16, Offset(4)))
+                .instruction(Instruction::Jump, Offset(2))
+                .data([0xAB_u8, 0xCD])
+                .build(),
+        )
+    }
+
+    fn consumer() -> Machine<InMemoryMemory> {
+        // Receives two messages into the space at the end of its memory
+        Machine::with_memory(
+            InMemoryMemory::builder()
+                .instruction(
+                    Instruction::Recv,
+                    (0_u8, OffsetPair(Offset(10), Offset(-10))),
+                )
+                .instruction(Instruction::Recv, (0_u8, OffsetPair(Offset(7), Offset(-7))))
+                .instruction(Instruction::Jump, Offset(7))
+                .data([0_u8; 7])
+                .build(),
+        )
+    }
+
+    #[test]
+    fn receiving_waits_for_a_message() {
+        let mut network = Network::new();
+        let consumer = network.add(consumer());
+        let producer = network.add(producer());
+        network.connect((producer, 0), (consumer, 0));
+        network.run().unwrap();
+
+        assert_eq!(
+            network.machines[consumer].memory.memory[17..],
+            [0x02, 0x00, 0xAB, 0xCD, 0x01, 0x00, 0xCD]
+        );
+        assert_eq!(network.machines[consumer].cpu.instruction_count, 3);
+    }
+
+    #[test]
+    fn messages_pass_along_a_pipeline() {
+        // The middle machine forwards the first message it receives
+        let relay = Machine::with_memory(
+            InMemoryMemory::builder()
+                .instruction(
+                    Instruction::Recv,
+                    (0_u8, OffsetPair(Offset(10), Offset(-10))),
+                )
+                .instruction(Instruction::Send, (1_u8, 2_u16, Offset(5)))
+                .instruction(Instruction::Jump, Offset(4))
+                .data([0_u8; 4])
+                .build(),
+        );
+        let mut network = Network::new().quantum(1);
+        let producer = network.add(producer());
+        let relay = network.add(relay);
+        let consumer = network.add(consumer());
+        let leftovers = network.connect((producer, 0), (relay, 0));
+        network.connect((relay, 1), (consumer, 0));
+
+        // The consumer only gets one message, so it waits forever
+        assert!(matches!(
+            network.run(),
+            Err(Trap::Fault {
+                fault: Fault::Deadlock,
+                ..
+            })
+        ));
+        assert_eq!(
+            network.machines[consumer].memory.memory[17..21],
+            [0x02, 0x00, 0xAB, 0xCD]
+        );
+        assert_eq!(leftovers.try_recv(), Some(vec![0xCD]));
+    }
+
+    #[test]
+    fn senders_cant_receive_their_own_messages() {
+        let mut network = Network::new();
+        let sender = network.add(consumer());
+        let receiver = network.add(consumer());
+        let channel = network.connect((sender, 0), (receiver, 0));
+        channel.send(vec![0x01]);
+
+        assert!(matches!(
+            network.run(),
+            Err(Trap::Fault {
+                fault: Fault::UnknownPort { port: 0 },
+                ..
+            })
+        ));
+        assert_eq!(network.current(), sender);
+        assert_eq!(channel.len(), 1);
+    }
+
+    #[test]
+    fn a_lone_machine_cant_wait_for_itself() {
+        let mut machine = consumer().port(0, Port::Receiver(Channel::new()));
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::Deadlock,
+                ..
+            })
+        ));
+        assert_eq!(machine.memory.pc, 0);
+    }
+}
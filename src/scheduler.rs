@@ -0,0 +1,322 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::machine::{step, Cpu, HostFunctions};
+use crate::memory::{Cursor, Storage};
+use crate::trap::{Fault, Trap};
+use crate::types::Register;
+
+/// What a thread instruction asked the scheduler to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadRequest {
+    /// Start a thread at `entry`, and put its id in the register `id`.
+    Spawn { entry: usize, id: Register },
+    /// Let another thread run.
+    Yield,
+    /// Wait until `thread` has finished.
+    Join { thread: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Runnable,
+    Joining(usize),
+    Finished,
+}
+
+/// The memory a thread runs on: its own cursor over the scheduler's storage.
+pub type ThreadMemory<S> = Cursor<Rc<RefCell<S>>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thread<S: Storage> {
+    pub cpu: Cpu,
+    pub memory: ThreadMemory<S>,
+    pub state: ThreadState,
+}
+
+/// How the scheduler picks the next thread to run. Both are deterministic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Runs each thread for `quantum` instructions (or until it yields), in
+    /// order of thread id.
+    RoundRobin { quantum: u32 },
+    /// Picks a thread at random before every instruction, from a generator
+    /// seeded with `seed`.
+    Random { seed: u64 },
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::RoundRobin { quantum: 100 }
+    }
+}
+
+/// Runs several threads over a single storage, each with a cursor of its
+/// own. Only one thread runs at a time.
+///
+/// The main thread has id 0 and starts at address 0. A thread finishes when
+/// it runs off the end of memory, and the scheduler stops once all of them
+/// have.
+#[derive(Debug)]
+pub struct Scheduler<S: Storage> {
+    pub storage: Rc<RefCell<S>>,
+    pub host: HostFunctions<ThreadMemory<S>>,
+    pub threads: Vec<Thread<S>>,
+    current: usize,
+    schedule: Schedule,
+    /// Instructions left in the current thread's quantum.
+    remaining: u32,
+    rng: u64,
+}
+
+impl<S: Storage> Scheduler<S> {
+    pub fn with_storage(storage: S) -> Self {
+        let storage = Rc::new(RefCell::new(storage));
+        Scheduler {
+            host: HostFunctions::default(),
+            threads: vec![Thread {
+                cpu: Cpu::default(),
+                memory: Cursor::new(storage.clone()),
+                state: ThreadState::Runnable,
+            }],
+            storage,
+            current: 0,
+            schedule: Schedule::default(),
+            remaining: 0,
+            rng: 0,
+        }
+    }
+
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        if let Schedule::Random { seed } = schedule {
+            // xorshift gets stuck at zero
+            self.rng = seed.max(1);
+        }
+        self
+    }
+
+    /// See `Machine::host_call`.
+    pub fn host_call(
+        mut self,
+        id: u16,
+        function: impl FnMut(&mut ThreadMemory<S>, usize) -> Result<u64, Trap<S::Error>> + 'static,
+    ) -> Self {
+        self.host.insert(id, Box::new(function));
+        self
+    }
+
+    /// The id of the thread that ran (or is about to run) the latest
+    /// instruction.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn run(&mut self) -> Result<(), Trap<S::Error>> {
+        while let Some(next) = self.pick()? {
+            self.current = next;
+
+            let thread = &mut self.threads[self.current];
+            if !step(&mut thread.memory, &mut thread.cpu, &mut self.host)? {
+                self.finish();
+                continue;
+            }
+            self.remaining = self.remaining.saturating_sub(1);
+            match thread.cpu.thread_request.take() {
+                None => {}
+                Some(ThreadRequest::Yield) => self.remaining = 0,
+                Some(ThreadRequest::Spawn { entry, id }) => self.spawn(entry, id)?,
+                Some(ThreadRequest::Join { thread }) => self.join(thread)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Chooses the thread to run next, or `None` if every thread has
+    /// finished.
+    fn pick(&mut self) -> Result<Option<usize>, Fault> {
+        let runnable = (0..self.threads.len())
+            .filter(|&id| {
+                self.threads[id].state == ThreadState::Runnable && !self.threads[id].cpu.waiting()
+            })
+            .collect::<Vec<_>>();
+        if runnable.is_empty() {
+            return match self
+                .threads
+                .iter()
+                .all(|thread| thread.state == ThreadState::Finished)
+            {
+                true => Ok(None),
+                false => Err(Fault::Deadlock),
+            };
+        }
+
+        match self.schedule {
+            Schedule::RoundRobin { quantum } => {
+                if self.remaining > 0 && runnable.contains(&self.current) {
+                    return Ok(Some(self.current));
+                }
+                self.remaining = quantum.max(1);
+                let next = runnable
+                    .iter()
+                    .find(|&&id| id > self.current)
+                    .unwrap_or(&runnable[0]);
+                Ok(Some(*next))
+            }
+            Schedule::Random { .. } => {
+                self.rng ^= self.rng << 13;
+                self.rng ^= self.rng >> 7;
+                self.rng ^= self.rng << 17;
+                Ok(Some(runnable[(self.rng % runnable.len() as u64) as usize]))
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.threads[self.current].state = ThreadState::Finished;
+        for thread in &mut self.threads {
+            if thread.state == ThreadState::Joining(self.current) {
+                thread.state = ThreadState::Runnable;
+            }
+        }
+        self.remaining = 0;
+    }
+
+    fn spawn(&mut self, entry: usize, id: Register) -> Result<(), Fault> {
+        let thread_id = self.threads.len();
+        let parent = &mut self.threads[self.current].cpu;
+        *parent.register(id)? = thread_id as u64;
+        let cpu = Cpu {
+            thread_id,
+            max_call_depth: parent.max_call_depth,
+            max_stack_size: parent.max_stack_size,
+            ports: parent.ports.clone(),
+            ..Cpu::default()
+        };
+        self.threads.push(Thread {
+            cpu,
+            memory: Cursor {
+                storage: self.storage.clone(),
+                position: entry,
+            },
+            state: ThreadState::Runnable,
+        });
+        Ok(())
+    }
+
+    fn join(&mut self, thread: usize) -> Result<(), Fault> {
+        match self.threads.get(thread).map(|thread| thread.state) {
+            None => Err(Fault::UnknownThread),
+            Some(ThreadState::Finished) => Ok(()),
+            Some(_) => {
+                self.threads[self.current].state = ThreadState::Joining(thread);
+                self.remaining = 0;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::Machine;
+    use crate::memory::InMemoryMemory;
+    use crate::types::{Instruction, Offset, OffsetPair};
+
+    use super::*;
+
+    fn spawn_and_join() -> InMemoryMemory {
+        // The main thread spawns a thread that stores its id at the end of
+        // memory, then waits for it before jumping to the end itself
+        InMemoryMemory::builder()
+            .instruction(Instruction::Spawn, (Offset(6), Register(0)))
+            .instruction(Instruction::Join, Register(0))
+            .instruction(Instruction::Jump, Offset(18))
+            .instruction(Instruction::ThreadId, Register(1))
+            .instruction(
+                Instruction::StoreRegister,
+                (Register(1), OffsetPair(Offset(0), Offset(0))),
+            )
+            .data(0_u64)
+            .build()
+    }
+
+    #[test]
+    fn spawned_threads_can_be_joined() {
+        let mut scheduler = Scheduler::with_storage(spawn_and_join());
+        scheduler.run().unwrap();
+
+        assert_eq!(
+            scheduler.storage.borrow().memory[21..29],
+            1_u64.to_le_bytes()
+        );
+        assert_eq!(scheduler.threads.len(), 2);
+        assert_eq!(scheduler.threads[0].cpu.registers[0], 1);
+        assert!(scheduler
+            .threads
+            .iter()
+            .all(|thread| thread.memory.position == 29));
+        assert!(scheduler
+            .threads
+            .iter()
+            .all(|thread| thread.state == ThreadState::Finished));
+    }
+
+    #[test]
+    fn random_schedules_are_reproducible() {
+        // Both threads race to overwrite the same byte with their id
+        let program = || {
+            InMemoryMemory::builder()
+                .instruction(Instruction::Spawn, (Offset(0), Register(0)))
+                .instruction(Instruction::ThreadId, Register(1))
+                .instruction(Instruction::Yield, ())
+                .instruction(
+                    Instruction::StoreRegister,
+                    (Register(1), OffsetPair(Offset(0), Offset(0))),
+                )
+                .data(0_u64)
+                .build()
+        };
+        let run = |seed| {
+            let mut scheduler =
+                Scheduler::with_storage(program()).schedule(Schedule::Random { seed });
+            scheduler.run().unwrap();
+            scheduler
+        };
+
+        for seed in [1, 2, 3] {
+            let (first, second) = (run(seed), run(seed));
+            assert!(first.storage.borrow().memory[17] <= 1);
+            assert_eq!(first.storage, second.storage);
+            assert_eq!(first.threads, second.threads);
+        }
+    }
+
+    #[test]
+    fn joining_yourself_deadlocks() {
+        let memory = InMemoryMemory::builder()
+            .instruction(Instruction::ThreadId, Register(0))
+            .instruction(Instruction::Join, Register(0))
+            .build();
+        let mut scheduler = Scheduler::with_storage(memory);
+        assert!(matches!(
+            scheduler.run(),
+            Err(Trap::Fault {
+                fault: Fault::Deadlock,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn machines_cannot_spawn_threads() {
+        let mut machine = Machine::with_memory(spawn_and_join());
+        assert!(matches!(
+            machine.run(),
+            Err(Trap::Fault {
+                fault: Fault::NoScheduler,
+                ..
+            })
+        ));
+    }
+}
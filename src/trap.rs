@@ -0,0 +1,147 @@
+use crate::memory::{Access, MemoryError};
+use crate::types::Instruction;
+
+/// Something the program tried to do that the machine refused to carry out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// An access touched a protected region that doesn't allow it.
+    Protection { address: usize, access: Access },
+    /// A checked arithmetic instruction overflowed.
+    Overflow,
+    /// A checked float to integer conversion was given NaN, or a value that
+    /// doesn't fit in the target type.
+    InvalidConversion,
+    /// A `Call` would have nested deeper than the machine's maximum call
+    /// depth, or a push would have grown the operand stack past its maximum
+    /// size.
+    StackOverflow,
+    /// A `Return` was executed with no call in progress, or a pop found too
+    /// few bytes on the operand stack.
+    StackUnderflow,
+    /// A register instruction named a register that doesn't exist.
+    InvalidRegister,
+    /// A `HostCall` named an id that no host function is registered as.
+    UnknownHostCall { id: u16 },
+    /// An integer division or remainder had a divisor of zero.
+    DivideByZero,
+    /// The opcode doesn't belong to any instruction.
+    InvalidOpcode,
+    /// Raised on purpose by the `SoftwareInterrupt` instruction.
+    SoftwareInterrupt,
+    /// A trap instruction was executed outside of a trap handler.
+    NotHandlingTrap,
+    /// `ReturnFromInterrupt` was executed outside of an interrupt handler.
+    NotHandlingInterrupt,
+    /// A thread instruction other than `Yield` was executed by a `Machine`,
+    /// which only has a single thread.
+    NoScheduler,
+    /// `Join` was given an id that no thread has.
+    UnknownThread,
+    /// Every thread or machine that hasn't finished is waiting to join
+    /// another, or to receive a message that nothing can send.
+    Deadlock,
+    /// An atomic instruction's target isn't aligned to its size, which
+    /// `SharedMemory` requires.
+    MisalignedAtomic { address: usize },
+    /// A channel instruction named a port that isn't connected, or whose end
+    /// of the channel only goes the other way.
+    UnknownPort { port: u8 },
+    /// An address was too far into memory to be written as an `Address`.
+    AddressOutOfRange { address: usize },
+}
+
+impl Fault {
+    /// The code identifying this kind of fault to trap handlers.
+    pub fn code(&self) -> u8 {
+        match self {
+            Fault::Protection { .. } => 0,
+            Fault::Overflow => 1,
+            Fault::InvalidConversion => 2,
+            Fault::StackOverflow => 3,
+            Fault::StackUnderflow => 4,
+            Fault::InvalidRegister => 5,
+            Fault::UnknownHostCall { .. } => 6,
+            Fault::DivideByZero => 7,
+            Fault::InvalidOpcode => 8,
+            Fault::SoftwareInterrupt => 9,
+            Fault::NotHandlingTrap => 10,
+            Fault::NotHandlingInterrupt => 11,
+            Fault::NoScheduler => 12,
+            Fault::UnknownThread => 13,
+            Fault::Deadlock => 14,
+            Fault::MisalignedAtomic { .. } => 15,
+            Fault::UnknownPort { .. } => 16,
+            Fault::AddressOutOfRange { .. } => 17,
+        }
+    }
+}
+
+/// The reason `Machine::run` stopped early.
+#[derive(Debug)]
+pub enum Trap<E> {
+    /// The memory backend failed (e.g. an I/O error).
+    Memory(E),
+    /// The program faulted. `instruction` is the instruction that was
+    /// executing, or `None` if the fault happened while fetching it.
+    /// `address` is where that instruction starts.
+    Fault {
+        fault: Fault,
+        instruction: Option<Instruction>,
+        address: Option<usize>,
+    },
+}
+
+impl<E> Trap<E> {
+    pub fn in_instruction(self, instruction: Instruction) -> Self {
+        match self {
+            Trap::Fault {
+                fault,
+                instruction: None,
+                address,
+            } => Trap::Fault {
+                fault,
+                instruction: Some(instruction),
+                address,
+            },
+            other => other,
+        }
+    }
+
+    pub fn at(self, address: usize) -> Self {
+        match self {
+            Trap::Fault {
+                fault,
+                instruction,
+                address: None,
+            } => Trap::Fault {
+                fault,
+                instruction,
+                address: Some(address),
+            },
+            other => other,
+        }
+    }
+}
+
+impl<E: MemoryError> From<E> for Trap<E> {
+    fn from(err: E) -> Self {
+        match err.fault() {
+            Some(fault) => Trap::Fault {
+                fault,
+                instruction: None,
+                address: None,
+            },
+            None => Trap::Memory(err),
+        }
+    }
+}
+
+impl<E> From<Fault> for Trap<E> {
+    fn from(fault: Fault) -> Self {
+        Trap::Fault {
+            fault,
+            instruction: None,
+            address: None,
+        }
+    }
+}
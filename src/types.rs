@@ -1,4 +1,7 @@
-use crate::memory::Memory;
+use crate::machine::{Cpu, HostFunctions};
+use crate::memory::{AtomicValue, Memory};
+use crate::scheduler::ThreadRequest;
+use crate::trap::{Fault, Trap};
 
 pub trait ReadWriteable {
     // NOTE: With full const generics, we can(?) replace
@@ -182,6 +185,48 @@ impl ReadWriteable for Offset {
     }
 }
 
+/// An absolute location in memory, for data that is too far away to reach
+/// with an `Offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Address(pub u32);
+
+impl Address {
+    /// Faults if `address` is too far into memory to fit.
+    pub fn try_from_usize(address: usize) -> Result<Self, Fault> {
+        u32::try_from(address)
+            .map(Address)
+            .map_err(|_| Fault::AddressOutOfRange { address })
+    }
+}
+
+impl ReadWriteable for Address {
+    const NUM_BYTES: usize = 4;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Address(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.0.to_le_bytes());
+    }
+}
+
+/// The index of one of the `Cpu`'s registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Register(pub u8);
+
+impl ReadWriteable for Register {
+    const NUM_BYTES: usize = 1;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Register(bytes[0])
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes[0] = self.0;
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct OffsetPair(pub Offset, pub Offset);
 
@@ -224,6 +269,18 @@ impl ReadWriteable for u64 {
     }
 }
 
+impl ReadWriteable for u16 {
+    const NUM_BYTES: usize = 2;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+}
+
 impl ReadWriteable for u32 {
     const NUM_BYTES: usize = 4;
 
@@ -236,6 +293,56 @@ impl ReadWriteable for u32 {
     }
 }
 
+impl ReadWriteable for i8 {
+    const NUM_BYTES: usize = 1;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        bytes[0] as i8
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes[0] = self as u8;
+    }
+}
+
+impl ReadWriteable for i16 {
+    const NUM_BYTES: usize = 2;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        i16::from_le_bytes([bytes[0], bytes[1]])
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl ReadWriteable for i32 {
+    const NUM_BYTES: usize = 4;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl ReadWriteable for i64 {
+    const NUM_BYTES: usize = 8;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        i64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ])
+    }
+
+    fn into_bytes(self, bytes: &mut [u8]) {
+        bytes.copy_from_slice(&self.to_le_bytes());
+    }
+}
+
 impl ReadWriteable for f32 {
     const NUM_BYTES: usize = 4;
 
@@ -262,45 +369,241 @@ impl ReadWriteable for f64 {
     }
 }
 
+/// Opcodes starting with one of these bytes are two bytes long, the first
+/// byte selecting a page of 256 extended instructions. Everything else is a
+/// single byte.
+pub const EXTENDED_PREFIX: u8 = 0xF0;
+
+/// Seeks by an offset that might not fit in a single `Offset`.
+fn seek_by<Mem: Memory>(mem: &mut Mem, mut offset: isize) -> Result<(), Mem::Error> {
+    while offset != 0 {
+        let step = offset.clamp(i16::MIN as isize, i16::MAX as isize);
+        mem.seek(Offset(step as i16))?;
+        offset -= step;
+    }
+    Ok(())
+}
+
+/// Reads the value the pointer at `pointer` points to, leaving the cursor
+/// where it started.
+fn load_indirect<Mem: Memory, T: ReadWriteable>(
+    mem: &mut Mem,
+    pointer: Offset,
+) -> Result<T, Mem::Error> {
+    mem.seek(pointer)?;
+    let target = mem.read::<Offset>()?;
+    mem.seek(target)?;
+    let value = mem.read::<T>()?;
+    seek_by(
+        mem,
+        -(pointer.0 as isize
+            + Offset::NUM_BYTES as isize
+            + target.0 as isize
+            + T::NUM_BYTES as isize),
+    )?;
+    Ok(value)
+}
+
+/// Writes to where the pointer at `pointer` points to, leaving the cursor
+/// where it started.
+fn store_indirect<Mem: Memory, T: ReadWriteable>(
+    mem: &mut Mem,
+    pointer: Offset,
+    value: T,
+) -> Result<(), Mem::Error> {
+    mem.seek(pointer)?;
+    let target = mem.read::<Offset>()?;
+    mem.seek(target)?;
+    mem.write(value)?;
+    seek_by(
+        mem,
+        -(pointer.0 as isize
+            + Offset::NUM_BYTES as isize
+            + target.0 as isize
+            + T::NUM_BYTES as isize),
+    )
+}
+
+/// Reads the value the absolute pointer at `pointer` points to, leaving the
+/// cursor where it started.
+fn load_indirect_absolute<Mem: Memory, T: ReadWriteable>(
+    mem: &mut Mem,
+    pointer: Offset,
+) -> Result<T, Mem::Error> {
+    let position = mem.position()?;
+    mem.seek(pointer)?;
+    let target = mem.read::<Address>()?;
+    mem.set_position(target.0 as usize)?;
+    let value = mem.read::<T>()?;
+    mem.set_position(position)?;
+    Ok(value)
+}
+
+/// Writes to where the absolute pointer at `pointer` points to, leaving the
+/// cursor where it started.
+fn store_indirect_absolute<Mem: Memory, T: ReadWriteable>(
+    mem: &mut Mem,
+    pointer: Offset,
+    value: T,
+) -> Result<(), Mem::Error> {
+    let position = mem.position()?;
+    mem.seek(pointer)?;
+    let target = mem.read::<Address>()?;
+    mem.set_position(target.0 as usize)?;
+    mem.write(value)?;
+    mem.set_position(position)
+}
+
+/// Integer division and remainder fault on a zero divisor rather than
+/// panicking. Dividing the minimum value by -1 wraps.
+fn nonzero<T: Default + PartialEq>(divisor: T) -> Result<T, Fault> {
+    if divisor == T::default() {
+        Err(Fault::DivideByZero)
+    } else {
+        Ok(divisor)
+    }
+}
+
+/// Copies `size` bytes between two absolute addresses, leaving the cursor where
+/// it started.
+fn move_absolute<Mem: Memory>(
+    mem: &mut Mem,
+    size: usize,
+    from: Address,
+    to: Address,
+) -> Result<(), Mem::Error> {
+    let position = mem.position()?;
+    mem.set_position(from.0 as usize)?;
+    let mut value = Vec::with_capacity(size);
+    while value.len() < size {
+        value.push(mem.read::<u8>()?);
+    }
+
+    mem.set_position(to.0 as usize)?;
+    for byte in value {
+        mem.write::<u8>(byte)?;
+    }
+    mem.set_position(position)
+}
+
+/// Writes a received message at the cursor, leaving the cursor where it
+/// started. Writes nothing for `None`.
+fn write_message<Mem: Memory>(mem: &mut Mem, message: Option<Vec<u8>>) -> Result<(), Mem::Error> {
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let len = u16::NUM_BYTES + message.len();
+    mem.write(message.len() as u16)?;
+    for byte in message {
+        mem.write::<u8>(byte)?;
+    }
+    seek_by(mem, -(len as isize))
+}
+
+/// Atomically updates the value at `target`, leaving the cursor where it
+/// started.
+fn atomic_at<Mem: Memory, T: AtomicValue>(
+    mem: &mut Mem,
+    target: Offset,
+    update: impl FnMut(T) -> Option<T>,
+) -> Result<T, Mem::Error> {
+    mem.seek(target)?;
+    let old = mem.atomic_update(update)?;
+    seek_by(mem, -(target.0 as isize + T::NUM_BYTES as isize))?;
+    Ok(old)
+}
+
 macro_rules! instructions {
-    ($($a:literal => $name:ident($($argname:ident: $argtype:ty),*) |$mem:ident| $block:expr,)+) => {
+    ($($a:literal => $name:ident($($argname:ident: $argtype:ty),*) |$mem:ident $(, $cpu:ident $(, $host:ident)?)?| $block:expr,)+) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u16)]
         pub enum Instruction {
             $($name = $a,)*
         }
 
         impl Instruction {
-            pub fn execute<Mem: Memory>(&self, mem: &mut Mem) -> Result<(), Mem::Error> {
+            /// Executes the instruction on a fresh `Cpu` with no host
+            /// functions, for instructions that only touch memory.
+            pub fn execute<Mem: Memory>(&self, mem: &mut Mem) -> Result<(), Trap<Mem::Error>> {
+                self.execute_with(mem, &mut Cpu::default(), &mut HostFunctions::default())
+            }
+
+            pub fn execute_with<Mem: Memory>(
+                &self,
+                mem: &mut Mem,
+                cpu: &mut Cpu,
+                host: &mut HostFunctions<Mem>,
+            ) -> Result<(), Trap<Mem::Error>> {
                 match *self {
                     $(Instruction::$name => {
                         $(let $argname = mem.read::<$argtype>()?;)*
                         let $mem = mem;
+                        $(
+                            let $cpu = &mut *cpu;
+                            $(let $host = &mut *host;)?
+                        )?
                         $block;
                     })*
                 }
                 Ok(())
             }
-        }
-
-        impl ReadWriteable for Instruction {
-            const NUM_BYTES: usize = 1;
 
-
-            fn from_bytes(bytes: &[u8]) -> Self {
-                match bytes[0] {
+            pub fn from_opcode(opcode: u16) -> Self {
+                match opcode {
                     $(x if x == $a => Instruction::$name,)*
-                    _ => Instruction::Noop,
+                    _ => Instruction::InvalidOpcode,
                 }
             }
 
-
-            fn into_bytes(self, bytes: &mut [u8]) {
-                bytes[0] = self as u8;
+            /// The number of bytes taken up by the instruction's operands.
+            pub fn operand_len(self) -> usize {
+                match self {
+                    $(Instruction::$name => 0 $(+ <$argtype as ReadWriteable>::NUM_BYTES)*,)*
+                }
             }
         }
     };
 }
 
+impl Instruction {
+    pub fn opcode(self) -> u16 {
+        self as u16
+    }
+
+    pub fn encoded_len(self) -> usize {
+        if self.opcode() > u8::MAX as u16 {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn encode(self, bytes: &mut [u8]) {
+        match self.encoded_len() {
+            1 => bytes[0] = self.opcode() as u8,
+            _ => bytes[0..2].copy_from_slice(&self.opcode().to_be_bytes()),
+        }
+    }
+
+    /// Reads the opcode at the cursor, or returns `None` if the end of memory
+    /// has been reached.
+    pub fn decode<Mem: Memory + ?Sized>(mem: &mut Mem) -> Result<Option<Self>, Mem::Error> {
+        let Some(first) = mem.read_if_present::<u8>()? else {
+            return Ok(None);
+        };
+        if first < EXTENDED_PREFIX {
+            return Ok(Some(Instruction::from_opcode(first as u16)));
+        }
+        let Some(second) = mem.read_if_present::<u8>()? else {
+            // A prefix at the very end of memory, with nothing to extend
+            return Ok(Some(Instruction::InvalidOpcode));
+        };
+        Ok(Some(Instruction::from_opcode(u16::from_be_bytes([
+            first, second,
+        ]))))
+    }
+}
+
 instructions! {
     0x00 => Noop() |_mem| {},
     0x01 => Jump(offset: Offset) |mem| {
@@ -311,76 +614,144 @@ instructions! {
             mem.seek(offset)?;
         }
     },
+    0x03 => JumpIndirect(pointer: Offset) |mem| {
+        mem.seek(pointer)?;
+        let target = mem.read::<Offset>()?;
+        mem.seek(target)?;
+    },
+    0x04 => JumpAbsolute(address: Address) |mem| {
+        mem.set_position(address.0 as usize)?;
+    },
+    0x05 => JumpIfAbsolute(cond: bool, address: Address) |mem| {
+        if cond {
+            mem.set_position(address.0 as usize)?;
+        }
+    },
+    // Return addresses are kept on the `Cpu`'s call stack rather than in
+    // memory, so a program can't clobber them by accident.
+    0x06 => Call(offset: Offset) |mem, cpu| {
+        cpu.push_call(mem.position()?)?;
+        mem.seek(offset)?;
+    },
+    0x07 => Return() |mem, cpu| {
+        mem.set_position(cpu.pop_call()?)?;
+    },
+    // `args` is passed to the host function as an absolute address. The
+    // function may move the cursor, so it's put back before `result` applies.
+    0x08 => HostCall(id: u16, args: Offset, result: OffsetPair) |mem, _cpu, host| {
+        let end = mem.position()?;
+        let value = host.call(id, mem, end.wrapping_add_signed(args.0 as isize))?;
+        mem.set_position(end)?;
+        mem.seek(result.0)?;
+        mem.write(value)?;
+        mem.seek(result.1)?;
+    },
+    // Trap handlers are jumped to instead of stopping the machine when a
+    // fault with their code is raised (see `Fault::code`). The handler can
+    // inspect the trap with `StoreTrapCode` and `StoreTrapAddress`, and
+    // `ReturnFromTrap` resumes after the faulting instruction.
+    0x09 => SetTrapHandler(code: u8, handler: Offset) |mem, cpu| {
+        let handler = mem.position()?.wrapping_add_signed(handler.0 as isize);
+        cpu.trap_handlers.insert(code, handler);
+    },
+    0x0A => ClearTrapHandler(code: u8) |_mem, cpu| {
+        cpu.trap_handlers.remove(&code);
+    },
+    0x0B => ReturnFromTrap() |mem, cpu| {
+        let frame = cpu.trap_frame.take().ok_or(Fault::NotHandlingTrap)?;
+        mem.set_position(frame.resume)?;
+    },
+    0x0C => StoreTrapCode(to: OffsetPair) |mem, cpu| {
+        let frame = cpu.trap_frame.ok_or(Fault::NotHandlingTrap)?;
+        mem.seek(to.0)?;
+        mem.write(frame.code)?;
+        mem.seek(to.1)?;
+    },
+    0x0D => StoreTrapAddress(to: OffsetPair) |mem, cpu| {
+        let frame = cpu.trap_frame.ok_or(Fault::NotHandlingTrap)?;
+        mem.seek(to.0)?;
+        mem.write(Address::try_from_usize(frame.address)?)?;
+        mem.seek(to.1)?;
+    },
+    0x0E => SoftwareInterrupt() |_mem| {
+        return Err(Fault::SoftwareInterrupt.into());
+    },
+    0x0F => JumpIndirectAbsolute(pointer: Offset) |mem| {
+        mem.seek(pointer)?;
+        let target = mem.read::<Address>()?;
+        mem.set_position(target.0 as usize)?;
+    },
 
+    // Unsigned integer arithmetic wraps around on overflow.
     0x10 => AddInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left + right)?;
+        mem.write(left.wrapping_add(right))?;
         mem.seek(output.1)?;
     },
     0x11 => SubtractInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left - right)?;
+        mem.write(left.wrapping_sub(right))?;
         mem.seek(output.1)?;
     },
     0x12 => MultiplyInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left * right)?;
+        mem.write(left.wrapping_mul(right))?;
         mem.seek(output.1)?;
     },
     0x13 => DivideUnsignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left / right)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
-    0x14 => DivideSignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+    0x14 => DivideSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write((left as i64 / right as i64) as u64)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
     0x15 => ModuloUnsignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left % right)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
-    0x16 => ModuloSignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+    0x16 => ModuloSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write((left as i64 % right as i64) as u64)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
 
     0x20 => AddInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left + right)?;
+        mem.write(left.wrapping_add(right))?;
         mem.seek(output.1)?;
     },
     0x21 => SubtractInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left - right)?;
+        mem.write(left.wrapping_sub(right))?;
         mem.seek(output.1)?;
     },
     0x22 => MultiplyInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left * right)?;
+        mem.write(left.wrapping_mul(right))?;
         mem.seek(output.1)?;
     },
     0x23 => DivideUnsignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left / right)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
-    0x24 => DivideSignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+    0x24 => DivideSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write((left as i32 / right as i32) as u32)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
     0x25 => ModuloUnsignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write(left % right)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
-    0x26 => ModuloSignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+    0x26 => ModuloSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
         mem.seek(output.0)?;
-        mem.write((left as i32 % right as i32) as u32)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
         mem.seek(output.1)?;
     },
 
@@ -445,6 +816,121 @@ instructions! {
         mem.seek(output.1)?;
     },
 
+    // Signed arithmetic. Wrapping variants wrap around on overflow (two's
+    // complement), checked variants raise an overflow fault instead and
+    // leave the output untouched.
+    0x40 => WrappingAddSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_add(right))?;
+        mem.seek(output.1)?;
+    },
+    0x41 => CheckedAddSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        let result = left.checked_add(right).ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x42 => WrappingSubtractSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_sub(right))?;
+        mem.seek(output.1)?;
+    },
+    0x43 => CheckedSubtractSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        let result = left.checked_sub(right).ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x44 => WrappingMultiplySignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_mul(right))?;
+        mem.seek(output.1)?;
+    },
+    0x45 => CheckedMultiplySignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        let result = left.checked_mul(right).ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x46 => WrappingNegateSignedInteger64(value: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.wrapping_neg())?;
+        mem.seek(output.1)?;
+    },
+    0x47 => CheckedNegateSignedInteger64(value: i64, output: OffsetPair) |mem| {
+        let result = value.checked_neg().ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x48 => WrappingAbsoluteSignedInteger64(value: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.wrapping_abs())?;
+        mem.seek(output.1)?;
+    },
+    0x49 => CheckedAbsoluteSignedInteger64(value: i64, output: OffsetPair) |mem| {
+        let result = value.checked_abs().ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+
+    0x50 => WrappingAddSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_add(right))?;
+        mem.seek(output.1)?;
+    },
+    0x51 => CheckedAddSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        let result = left.checked_add(right).ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x52 => WrappingSubtractSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_sub(right))?;
+        mem.seek(output.1)?;
+    },
+    0x53 => CheckedSubtractSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        let result = left.checked_sub(right).ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x54 => WrappingMultiplySignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_mul(right))?;
+        mem.seek(output.1)?;
+    },
+    0x55 => CheckedMultiplySignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        let result = left.checked_mul(right).ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x56 => WrappingNegateSignedInteger32(value: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.wrapping_neg())?;
+        mem.seek(output.1)?;
+    },
+    0x57 => CheckedNegateSignedInteger32(value: i32, output: OffsetPair) |mem| {
+        let result = value.checked_neg().ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+    0x58 => WrappingAbsoluteSignedInteger32(value: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.wrapping_abs())?;
+        mem.seek(output.1)?;
+    },
+    0x59 => CheckedAbsoluteSignedInteger32(value: i32, output: OffsetPair) |mem| {
+        let result = value.checked_abs().ok_or(Fault::Overflow)?;
+        mem.seek(output.0)?;
+        mem.write(result)?;
+        mem.seek(output.1)?;
+    },
+
     0x60 => Move1(from: Offset, to: Offset) |mem| {
         mem.seek(from)?;
         let value = mem.read::<[u8; 1]>()?;
@@ -481,62 +967,3095 @@ instructions! {
             mem.write::<u8>(byte)?;
         }
     },
-    0xA0 => PrintFloat() |_mem| {todo!()},
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::memory::InMemoryMemory;
+    // Indirect loads and stores follow a pointer stored in memory. `pointer`
+    // gives the location of the pointer, and the pointer itself is an
+    // `Offset` relative to the end of the pointer. As the target is only known
+    // at runtime, these return to the end of the instruction by themselves
+    // before `to` is applied.
+    0x68 => LoadIndirect1(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect::<_, [u8; 1]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x69 => LoadIndirect2(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect::<_, [u8; 2]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x6A => LoadIndirect4(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect::<_, [u8; 4]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x6B => LoadIndirect8(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect::<_, [u8; 8]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x6C => StoreIndirect1(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 1]>()?;
+        seek_by(mem, -(from.0 as isize + 1))?;
+        store_indirect(mem, pointer, value)?;
+    },
+    0x6D => StoreIndirect2(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 2]>()?;
+        seek_by(mem, -(from.0 as isize + 2))?;
+        store_indirect(mem, pointer, value)?;
+    },
+    0x6E => StoreIndirect4(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 4]>()?;
+        seek_by(mem, -(from.0 as isize + 4))?;
+        store_indirect(mem, pointer, value)?;
+    },
+    0x6F => StoreIndirect8(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 8]>()?;
+        seek_by(mem, -(from.0 as isize + 8))?;
+        store_indirect(mem, pointer, value)?;
+    },
+    // 16- and 8-bit arithmetic wraps around on overflow, which is usually
+    // what byte-oriented code (counters, string manipulation) wants.
+    0x70 => AddInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_add(right))?;
+        mem.seek(output.1)?;
+    },
+    0x71 => SubtractInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_sub(right))?;
+        mem.seek(output.1)?;
+    },
+    0x72 => MultiplyInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_mul(right))?;
+        mem.seek(output.1)?;
+    },
+    0x73 => DivideUnsignedInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+    0x74 => DivideSignedInteger16(left: i16, right: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+    0x75 => ModuloUnsignedInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+    0x76 => ModuloSignedInteger16(left: i16, right: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+
+    0x80 => AddInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_add(right))?;
+        mem.seek(output.1)?;
+    },
+    0x81 => SubtractInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_sub(right))?;
+        mem.seek(output.1)?;
+    },
+    0x82 => MultiplyInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_mul(right))?;
+        mem.seek(output.1)?;
+    },
+    0x83 => DivideUnsignedInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+    0x84 => DivideSignedInteger8(left: i8, right: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_div(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+    0x85 => ModuloUnsignedInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+    0x86 => ModuloSignedInteger8(left: i8, right: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.wrapping_rem(nonzero(right)?))?;
+        mem.seek(output.1)?;
+    },
+
+    // Absolute moves can't carry on from where the write ended like the
+    // relative ones do, so execution continues after the instruction.
+    0x90 => MoveAbsolute1(from: Address, to: Address) |mem| {
+        move_absolute(mem, 1, from, to)?;
+    },
+    0x91 => MoveAbsolute2(from: Address, to: Address) |mem| {
+        move_absolute(mem, 2, from, to)?;
+    },
+    0x92 => MoveAbsolute4(from: Address, to: Address) |mem| {
+        move_absolute(mem, 4, from, to)?;
+    },
+    0x93 => MoveAbsolute8(from: Address, to: Address) |mem| {
+        move_absolute(mem, 8, from, to)?;
+    },
+    0x94 => MoveAbsoluteN(size: u8, from: Address, to: Address) |mem| {
+        move_absolute(mem, size as usize, from, to)?;
+    },
+
+    // Like the indirect loads and stores above, but the pointer is an
+    // `Address` rather than an `Offset`.
+    0x95 => LoadIndirectAbsolute1(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect_absolute::<_, [u8; 1]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x96 => LoadIndirectAbsolute2(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect_absolute::<_, [u8; 2]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x97 => LoadIndirectAbsolute4(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect_absolute::<_, [u8; 4]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x98 => LoadIndirectAbsolute8(pointer: Offset, to: OffsetPair) |mem| {
+        let value = load_indirect_absolute::<_, [u8; 8]>(mem, pointer)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0x99 => StoreIndirectAbsolute1(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 1]>()?;
+        seek_by(mem, -(from.0 as isize + 1))?;
+        store_indirect_absolute(mem, pointer, value)?;
+    },
+    0x9A => StoreIndirectAbsolute2(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 2]>()?;
+        seek_by(mem, -(from.0 as isize + 2))?;
+        store_indirect_absolute(mem, pointer, value)?;
+    },
+    0x9B => StoreIndirectAbsolute4(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 4]>()?;
+        seek_by(mem, -(from.0 as isize + 4))?;
+        store_indirect_absolute(mem, pointer, value)?;
+    },
+    0x9C => StoreIndirectAbsolute8(from: Offset, pointer: Offset) |mem| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 8]>()?;
+        seek_by(mem, -(from.0 as isize + 8))?;
+        store_indirect_absolute(mem, pointer, value)?;
+    },
+
+    0xA0 => PrintFloat() |_mem| {todo!()},
+
+    // Bitwise instructions. Shifting by at least the width of the type
+    // shifts every bit out (so gives zero, or all sign bits for arithmetic
+    // shifts), rotations wrap around. Bit counts are written as a u8.
+    0xF000 => AndInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left & right)?;
+        mem.seek(output.1)?;
+    },
+    0xF001 => OrInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left | right)?;
+        mem.seek(output.1)?;
+    },
+    0xF002 => XorInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left ^ right)?;
+        mem.seek(output.1)?;
+    },
+    0xF003 => NotInteger64(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(!value)?;
+        mem.seek(output.1)?;
+    },
+    0xF004 => ShiftLeftInteger64(value: u64, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shl(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF005 => ShiftRightLogicalInteger64(value: u64, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shr(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF006 => ShiftRightArithmeticInteger64(value: u64, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write((value as i64).checked_shr(amount as u32).unwrap_or((value as i64) >> (64 - 1)) as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF007 => RotateLeftInteger64(value: u64, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_left(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF008 => RotateRightInteger64(value: u64, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_right(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF009 => CountOnesInteger64(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.count_ones() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF00A => LeadingZerosInteger64(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.leading_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF00B => TrailingZerosInteger64(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trailing_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF00C => ByteSwapInteger64(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.swap_bytes())?;
+        mem.seek(output.1)?;
+    },
+
+    0xF010 => AndInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left & right)?;
+        mem.seek(output.1)?;
+    },
+    0xF011 => OrInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left | right)?;
+        mem.seek(output.1)?;
+    },
+    0xF012 => XorInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left ^ right)?;
+        mem.seek(output.1)?;
+    },
+    0xF013 => NotInteger32(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(!value)?;
+        mem.seek(output.1)?;
+    },
+    0xF014 => ShiftLeftInteger32(value: u32, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shl(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF015 => ShiftRightLogicalInteger32(value: u32, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shr(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF016 => ShiftRightArithmeticInteger32(value: u32, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write((value as i32).checked_shr(amount as u32).unwrap_or((value as i32) >> (32 - 1)) as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF017 => RotateLeftInteger32(value: u32, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_left(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF018 => RotateRightInteger32(value: u32, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_right(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF019 => CountOnesInteger32(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.count_ones() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF01A => LeadingZerosInteger32(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.leading_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF01B => TrailingZerosInteger32(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trailing_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF01C => ByteSwapInteger32(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.swap_bytes())?;
+        mem.seek(output.1)?;
+    },
+
+    0xF020 => AndInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left & right)?;
+        mem.seek(output.1)?;
+    },
+    0xF021 => OrInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left | right)?;
+        mem.seek(output.1)?;
+    },
+    0xF022 => XorInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left ^ right)?;
+        mem.seek(output.1)?;
+    },
+    0xF023 => NotInteger16(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(!value)?;
+        mem.seek(output.1)?;
+    },
+    0xF024 => ShiftLeftInteger16(value: u16, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shl(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF025 => ShiftRightLogicalInteger16(value: u16, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shr(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF026 => ShiftRightArithmeticInteger16(value: u16, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write((value as i16).checked_shr(amount as u32).unwrap_or((value as i16) >> (16 - 1)) as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF027 => RotateLeftInteger16(value: u16, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_left(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF028 => RotateRightInteger16(value: u16, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_right(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF029 => CountOnesInteger16(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.count_ones() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF02A => LeadingZerosInteger16(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.leading_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF02B => TrailingZerosInteger16(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trailing_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF02C => ByteSwapInteger16(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.swap_bytes())?;
+        mem.seek(output.1)?;
+    },
+
+    0xF030 => AndInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left & right)?;
+        mem.seek(output.1)?;
+    },
+    0xF031 => OrInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left | right)?;
+        mem.seek(output.1)?;
+    },
+    0xF032 => XorInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left ^ right)?;
+        mem.seek(output.1)?;
+    },
+    0xF033 => NotInteger8(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(!value)?;
+        mem.seek(output.1)?;
+    },
+    0xF034 => ShiftLeftInteger8(value: u8, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shl(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF035 => ShiftRightLogicalInteger8(value: u8, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.checked_shr(amount as u32).unwrap_or(0))?;
+        mem.seek(output.1)?;
+    },
+    0xF036 => ShiftRightArithmeticInteger8(value: u8, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write((value as i8).checked_shr(amount as u32).unwrap_or((value as i8) >> (8 - 1)) as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF037 => RotateLeftInteger8(value: u8, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_left(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF038 => RotateRightInteger8(value: u8, amount: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.rotate_right(amount as u32))?;
+        mem.seek(output.1)?;
+    },
+    0xF039 => CountOnesInteger8(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.count_ones() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF03A => LeadingZerosInteger8(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.leading_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF03B => TrailingZerosInteger8(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trailing_zeros() as u8)?;
+        mem.seek(output.1)?;
+    },
+
+    // Comparisons write a bool, which can be used as the condition for
+    // `JumpIf`. Float comparisons follow IEEE 754, so any comparison with NaN
+    // is false, except for `NotEqual`, which is true.
+    0xF100 => EqualInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left == right)?;
+        mem.seek(output.1)?;
+    },
+    0xF101 => NotEqualInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left != right)?;
+        mem.seek(output.1)?;
+    },
+    0xF102 => LessThanUnsignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF103 => LessOrEqualUnsignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF104 => GreaterThanUnsignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF105 => GreaterOrEqualUnsignedInteger64(left: u64, right: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF106 => LessThanSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF107 => LessOrEqualSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF108 => GreaterThanSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF109 => GreaterOrEqualSignedInteger64(left: i64, right: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+
+    0xF110 => EqualInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left == right)?;
+        mem.seek(output.1)?;
+    },
+    0xF111 => NotEqualInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left != right)?;
+        mem.seek(output.1)?;
+    },
+    0xF112 => LessThanUnsignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF113 => LessOrEqualUnsignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF114 => GreaterThanUnsignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF115 => GreaterOrEqualUnsignedInteger32(left: u32, right: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF116 => LessThanSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF117 => LessOrEqualSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF118 => GreaterThanSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF119 => GreaterOrEqualSignedInteger32(left: i32, right: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+
+    0xF120 => EqualInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left == right)?;
+        mem.seek(output.1)?;
+    },
+    0xF121 => NotEqualInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left != right)?;
+        mem.seek(output.1)?;
+    },
+    0xF122 => LessThanUnsignedInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF123 => LessOrEqualUnsignedInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF124 => GreaterThanUnsignedInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF125 => GreaterOrEqualUnsignedInteger16(left: u16, right: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF126 => LessThanSignedInteger16(left: i16, right: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF127 => LessOrEqualSignedInteger16(left: i16, right: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF128 => GreaterThanSignedInteger16(left: i16, right: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF129 => GreaterOrEqualSignedInteger16(left: i16, right: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+
+    0xF130 => EqualInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left == right)?;
+        mem.seek(output.1)?;
+    },
+    0xF131 => NotEqualInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left != right)?;
+        mem.seek(output.1)?;
+    },
+    0xF132 => LessThanUnsignedInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF133 => LessOrEqualUnsignedInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF134 => GreaterThanUnsignedInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF135 => GreaterOrEqualUnsignedInteger8(left: u8, right: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF136 => LessThanSignedInteger8(left: i8, right: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF137 => LessOrEqualSignedInteger8(left: i8, right: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF138 => GreaterThanSignedInteger8(left: i8, right: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF139 => GreaterOrEqualSignedInteger8(left: i8, right: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+
+    0xF140 => EqualFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left == right)?;
+        mem.seek(output.1)?;
+    },
+    0xF141 => NotEqualFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left != right)?;
+        mem.seek(output.1)?;
+    },
+    0xF142 => LessThanFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF143 => LessOrEqualFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF144 => GreaterThanFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF145 => GreaterOrEqualFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+
+    0xF150 => EqualFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left == right)?;
+        mem.seek(output.1)?;
+    },
+    0xF151 => NotEqualFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left != right)?;
+        mem.seek(output.1)?;
+    },
+    0xF152 => LessThanFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left < right)?;
+        mem.seek(output.1)?;
+    },
+    0xF153 => LessOrEqualFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left <= right)?;
+        mem.seek(output.1)?;
+    },
+    0xF154 => GreaterThanFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left > right)?;
+        mem.seek(output.1)?;
+    },
+    0xF155 => GreaterOrEqualFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left >= right)?;
+        mem.seek(output.1)?;
+    },
+
+    // Integer conversions. Extending zero- or sign-fills the new high bits,
+    // truncating drops them. Between signed and unsigned integers of the same
+    // width no conversion is needed, the bits are simply reinterpreted.
+    0xF200 => ZeroExtendInteger8To16(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF201 => ZeroExtendInteger8To32(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF202 => ZeroExtendInteger8To64(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF203 => ZeroExtendInteger16To32(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF204 => ZeroExtendInteger16To64(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF205 => ZeroExtendInteger32To64(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF206 => SignExtendInteger8To16(value: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF207 => SignExtendInteger8To32(value: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF208 => SignExtendInteger8To64(value: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF209 => SignExtendInteger16To32(value: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF20A => SignExtendInteger16To64(value: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF20B => SignExtendInteger32To64(value: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF20C => TruncateInteger16To8(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF20D => TruncateInteger32To8(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF20E => TruncateInteger32To16(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF20F => TruncateInteger64To8(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF210 => TruncateInteger64To16(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF211 => TruncateInteger64To32(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+
+    // Integer to float conversions round to the nearest representable value,
+    // as does narrowing f64 to f32.
+    0xF220 => ConvertUnsignedInteger8ToFloat32(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF221 => ConvertUnsignedInteger8ToFloat64(value: u8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF222 => ConvertUnsignedInteger16ToFloat32(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF223 => ConvertUnsignedInteger16ToFloat64(value: u16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF224 => ConvertUnsignedInteger32ToFloat32(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF225 => ConvertUnsignedInteger32ToFloat64(value: u32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF226 => ConvertUnsignedInteger64ToFloat32(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF227 => ConvertUnsignedInteger64ToFloat64(value: u64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF228 => ConvertSignedInteger8ToFloat32(value: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF229 => ConvertSignedInteger8ToFloat64(value: i8, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF22A => ConvertSignedInteger16ToFloat32(value: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF22B => ConvertSignedInteger16ToFloat64(value: i16, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF22C => ConvertSignedInteger32ToFloat32(value: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF22D => ConvertSignedInteger32ToFloat64(value: i32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF22E => ConvertSignedInteger64ToFloat32(value: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+    0xF22F => ConvertSignedInteger64ToFloat64(value: i64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+
+    0xF230 => ConvertFloat32ToFloat64(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f64)?;
+        mem.seek(output.1)?;
+    },
+    0xF231 => ConvertFloat64ToFloat32(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value as f32)?;
+        mem.seek(output.1)?;
+    },
+
+    // Float to integer conversions either truncate towards zero, or round to
+    // the nearest integer with ties going to the even integer. Saturating
+    // variants clamp out of range values to the target's MIN/MAX and turn
+    // NaN into 0, checked variants raise an invalid conversion fault instead.
+    0xF240 => SaturatingTruncateFloat32ToUnsignedInteger8(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF241 => CheckedTruncateFloat32ToUnsignedInteger8(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u8::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF242 => SaturatingRoundFloat32ToUnsignedInteger8(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF243 => CheckedRoundFloat32ToUnsignedInteger8(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u8::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF244 => SaturatingTruncateFloat32ToUnsignedInteger16(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF245 => CheckedTruncateFloat32ToUnsignedInteger16(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u16::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF246 => SaturatingRoundFloat32ToUnsignedInteger16(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF247 => CheckedRoundFloat32ToUnsignedInteger16(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u16::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF248 => SaturatingTruncateFloat32ToUnsignedInteger32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF249 => CheckedTruncateFloat32ToUnsignedInteger32(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u32::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF24A => SaturatingRoundFloat32ToUnsignedInteger32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF24B => CheckedRoundFloat32ToUnsignedInteger32(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u32::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF24C => SaturatingTruncateFloat32ToUnsignedInteger64(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF24D => CheckedTruncateFloat32ToUnsignedInteger64(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u64::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF24E => SaturatingRoundFloat32ToUnsignedInteger64(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF24F => CheckedRoundFloat32ToUnsignedInteger64(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u64::MAX as f32 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF250 => SaturatingTruncateFloat32ToSignedInteger8(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF251 => CheckedTruncateFloat32ToSignedInteger8(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i8::MIN as f32 && value < -(i8::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF252 => SaturatingRoundFloat32ToSignedInteger8(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF253 => CheckedRoundFloat32ToSignedInteger8(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i8::MIN as f32 && value < -(i8::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF254 => SaturatingTruncateFloat32ToSignedInteger16(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF255 => CheckedTruncateFloat32ToSignedInteger16(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i16::MIN as f32 && value < -(i16::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF256 => SaturatingRoundFloat32ToSignedInteger16(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF257 => CheckedRoundFloat32ToSignedInteger16(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i16::MIN as f32 && value < -(i16::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF258 => SaturatingTruncateFloat32ToSignedInteger32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF259 => CheckedTruncateFloat32ToSignedInteger32(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i32::MIN as f32 && value < -(i32::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF25A => SaturatingRoundFloat32ToSignedInteger32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF25B => CheckedRoundFloat32ToSignedInteger32(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i32::MIN as f32 && value < -(i32::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF25C => SaturatingTruncateFloat32ToSignedInteger64(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF25D => CheckedTruncateFloat32ToSignedInteger64(value: f32, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i64::MIN as f32 && value < -(i64::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF25E => SaturatingRoundFloat32ToSignedInteger64(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF25F => CheckedRoundFloat32ToSignedInteger64(value: f32, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i64::MIN as f32 && value < -(i64::MIN as f32)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF260 => SaturatingTruncateFloat64ToUnsignedInteger8(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF261 => CheckedTruncateFloat64ToUnsignedInteger8(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u8::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF262 => SaturatingRoundFloat64ToUnsignedInteger8(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF263 => CheckedRoundFloat64ToUnsignedInteger8(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u8::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u8)?;
+        mem.seek(output.1)?;
+    },
+    0xF264 => SaturatingTruncateFloat64ToUnsignedInteger16(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF265 => CheckedTruncateFloat64ToUnsignedInteger16(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u16::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF266 => SaturatingRoundFloat64ToUnsignedInteger16(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF267 => CheckedRoundFloat64ToUnsignedInteger16(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u16::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u16)?;
+        mem.seek(output.1)?;
+    },
+    0xF268 => SaturatingTruncateFloat64ToUnsignedInteger32(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF269 => CheckedTruncateFloat64ToUnsignedInteger32(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u32::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF26A => SaturatingRoundFloat64ToUnsignedInteger32(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF26B => CheckedRoundFloat64ToUnsignedInteger32(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u32::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u32)?;
+        mem.seek(output.1)?;
+    },
+    0xF26C => SaturatingTruncateFloat64ToUnsignedInteger64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF26D => CheckedTruncateFloat64ToUnsignedInteger64(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= 0.0 && value < u64::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF26E => SaturatingRoundFloat64ToUnsignedInteger64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF26F => CheckedRoundFloat64ToUnsignedInteger64(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= 0.0 && value < u64::MAX as f64 + 1.0) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as u64)?;
+        mem.seek(output.1)?;
+    },
+    0xF270 => SaturatingTruncateFloat64ToSignedInteger8(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF271 => CheckedTruncateFloat64ToSignedInteger8(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i8::MIN as f64 && value < -(i8::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF272 => SaturatingRoundFloat64ToSignedInteger8(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF273 => CheckedRoundFloat64ToSignedInteger8(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i8::MIN as f64 && value < -(i8::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i8)?;
+        mem.seek(output.1)?;
+    },
+    0xF274 => SaturatingTruncateFloat64ToSignedInteger16(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF275 => CheckedTruncateFloat64ToSignedInteger16(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i16::MIN as f64 && value < -(i16::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF276 => SaturatingRoundFloat64ToSignedInteger16(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF277 => CheckedRoundFloat64ToSignedInteger16(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i16::MIN as f64 && value < -(i16::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i16)?;
+        mem.seek(output.1)?;
+    },
+    0xF278 => SaturatingTruncateFloat64ToSignedInteger32(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF279 => CheckedTruncateFloat64ToSignedInteger32(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i32::MIN as f64 && value < -(i32::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF27A => SaturatingRoundFloat64ToSignedInteger32(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF27B => CheckedRoundFloat64ToSignedInteger32(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i32::MIN as f64 && value < -(i32::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i32)?;
+        mem.seek(output.1)?;
+    },
+    0xF27C => SaturatingTruncateFloat64ToSignedInteger64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc() as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF27D => CheckedTruncateFloat64ToSignedInteger64(value: f64, output: OffsetPair) |mem| {
+        let value = value.trunc();
+        if !(value >= i64::MIN as f64 && value < -(i64::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF27E => SaturatingRoundFloat64ToSignedInteger64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round_ties_even() as i64)?;
+        mem.seek(output.1)?;
+    },
+    0xF27F => CheckedRoundFloat64ToSignedInteger64(value: f64, output: OffsetPair) |mem| {
+        let value = value.round_ties_even();
+        if !(value >= i64::MIN as f64 && value < -(i64::MIN as f64)) {
+            return Err(Fault::InvalidConversion.into());
+        }
+        mem.seek(output.0)?;
+        mem.write(value as i64)?;
+        mem.seek(output.1)?;
+    },
+
+    // Float maths, with the same semantics as the Rust standard library:
+    // `Round` rounds half-way cases away from zero, `Minimum`/`Maximum`
+    // return the other operand if one of them is NaN, and `ArcTangent2`
+    // computes atan2(left, right) with `left` as the y coordinate. The
+    // classification instructions write a bool.
+    0xF300 => SquareRootFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.sqrt())?;
+        mem.seek(output.1)?;
+    },
+    0xF301 => AbsoluteFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.abs())?;
+        mem.seek(output.1)?;
+    },
+    0xF302 => FloorFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.floor())?;
+        mem.seek(output.1)?;
+    },
+    0xF303 => CeilingFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.ceil())?;
+        mem.seek(output.1)?;
+    },
+    0xF304 => RoundFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round())?;
+        mem.seek(output.1)?;
+    },
+    0xF305 => TruncateFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc())?;
+        mem.seek(output.1)?;
+    },
+    0xF306 => SineFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.sin())?;
+        mem.seek(output.1)?;
+    },
+    0xF307 => CosineFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.cos())?;
+        mem.seek(output.1)?;
+    },
+    0xF308 => TangentFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.tan())?;
+        mem.seek(output.1)?;
+    },
+    0xF309 => ExponentialFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.exp())?;
+        mem.seek(output.1)?;
+    },
+    0xF30A => NaturalLogarithmFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.ln())?;
+        mem.seek(output.1)?;
+    },
+    0xF30B => Logarithm2Float32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.log2())?;
+        mem.seek(output.1)?;
+    },
+    0xF30C => MinimumFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.min(right))?;
+        mem.seek(output.1)?;
+    },
+    0xF30D => MaximumFloat32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.max(right))?;
+        mem.seek(output.1)?;
+    },
+    0xF30E => ArcTangent2Float32(left: f32, right: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.atan2(right))?;
+        mem.seek(output.1)?;
+    },
+    0xF30F => IsNanFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.is_nan())?;
+        mem.seek(output.1)?;
+    },
+    0xF310 => IsFiniteFloat32(value: f32, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.is_finite())?;
+        mem.seek(output.1)?;
+    },
+
+    0xF320 => SquareRootFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.sqrt())?;
+        mem.seek(output.1)?;
+    },
+    0xF321 => AbsoluteFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.abs())?;
+        mem.seek(output.1)?;
+    },
+    0xF322 => FloorFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.floor())?;
+        mem.seek(output.1)?;
+    },
+    0xF323 => CeilingFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.ceil())?;
+        mem.seek(output.1)?;
+    },
+    0xF324 => RoundFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.round())?;
+        mem.seek(output.1)?;
+    },
+    0xF325 => TruncateFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.trunc())?;
+        mem.seek(output.1)?;
+    },
+    0xF326 => SineFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.sin())?;
+        mem.seek(output.1)?;
+    },
+    0xF327 => CosineFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.cos())?;
+        mem.seek(output.1)?;
+    },
+    0xF328 => TangentFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.tan())?;
+        mem.seek(output.1)?;
+    },
+    0xF329 => ExponentialFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.exp())?;
+        mem.seek(output.1)?;
+    },
+    0xF32A => NaturalLogarithmFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.ln())?;
+        mem.seek(output.1)?;
+    },
+    0xF32B => Logarithm2Float64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.log2())?;
+        mem.seek(output.1)?;
+    },
+    0xF32C => MinimumFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.min(right))?;
+        mem.seek(output.1)?;
+    },
+    0xF32D => MaximumFloat64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.max(right))?;
+        mem.seek(output.1)?;
+    },
+    0xF32E => ArcTangent2Float64(left: f64, right: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(left.atan2(right))?;
+        mem.seek(output.1)?;
+    },
+    0xF32F => IsNanFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.is_nan())?;
+        mem.seek(output.1)?;
+    },
+    0xF330 => IsFiniteFloat64(value: f64, output: OffsetPair) |mem| {
+        mem.seek(output.0)?;
+        mem.write(value.is_finite())?;
+        mem.seek(output.1)?;
+    },
+
+    // Stack instructions work on the `Cpu`'s operand stack. `Push` takes its
+    // value from the instruction itself, while `PushFrom` reads it from memory
+    // and returns to the end of the instruction.
+    0xF400 => Push1(value: [u8; 1]) |_mem, cpu| {
+        cpu.push(value)?;
+    },
+    0xF401 => Push2(value: [u8; 2]) |_mem, cpu| {
+        cpu.push(value)?;
+    },
+    0xF402 => Push4(value: [u8; 4]) |_mem, cpu| {
+        cpu.push(value)?;
+    },
+    0xF403 => Push8(value: [u8; 8]) |_mem, cpu| {
+        cpu.push(value)?;
+    },
+    0xF404 => PushFrom1(from: Offset) |mem, cpu| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 1]>()?;
+        seek_by(mem, -(from.0 as isize + 1))?;
+        cpu.push(value)?;
+    },
+    0xF405 => PushFrom2(from: Offset) |mem, cpu| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 2]>()?;
+        seek_by(mem, -(from.0 as isize + 2))?;
+        cpu.push(value)?;
+    },
+    0xF406 => PushFrom4(from: Offset) |mem, cpu| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 4]>()?;
+        seek_by(mem, -(from.0 as isize + 4))?;
+        cpu.push(value)?;
+    },
+    0xF407 => PushFrom8(from: Offset) |mem, cpu| {
+        mem.seek(from)?;
+        let value = mem.read::<[u8; 8]>()?;
+        seek_by(mem, -(from.0 as isize + 8))?;
+        cpu.push(value)?;
+    },
+    0xF408 => Pop1(to: OffsetPair) |mem, cpu| {
+        let value = cpu.pop::<[u8; 1]>()?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0xF409 => Pop2(to: OffsetPair) |mem, cpu| {
+        let value = cpu.pop::<[u8; 2]>()?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0xF40A => Pop4(to: OffsetPair) |mem, cpu| {
+        let value = cpu.pop::<[u8; 4]>()?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0xF40B => Pop8(to: OffsetPair) |mem, cpu| {
+        let value = cpu.pop::<[u8; 8]>()?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+
+    // Stack arithmetic pops the right operand, then the left, and pushes the
+    // result. Integer arithmetic wraps around on overflow.
+    0xF410 => AddStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<u64>()?;
+        let left = cpu.pop::<u64>()?;
+        cpu.push(left.wrapping_add(right))?;
+    },
+    0xF411 => SubtractStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<u64>()?;
+        let left = cpu.pop::<u64>()?;
+        cpu.push(left.wrapping_sub(right))?;
+    },
+    0xF412 => MultiplyStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<u64>()?;
+        let left = cpu.pop::<u64>()?;
+        cpu.push(left.wrapping_mul(right))?;
+    },
+    0xF413 => DivideUnsignedStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<u64>()?;
+        let left = cpu.pop::<u64>()?;
+        cpu.push(left.wrapping_div(nonzero(right)?))?;
+    },
+    0xF414 => DivideSignedStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<i64>()?;
+        let left = cpu.pop::<i64>()?;
+        cpu.push(left.wrapping_div(nonzero(right)?))?;
+    },
+    0xF415 => ModuloUnsignedStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<u64>()?;
+        let left = cpu.pop::<u64>()?;
+        cpu.push(left.wrapping_rem(nonzero(right)?))?;
+    },
+    0xF416 => ModuloSignedStackInteger64() |_mem, cpu| {
+        let right = cpu.pop::<i64>()?;
+        let left = cpu.pop::<i64>()?;
+        cpu.push(left.wrapping_rem(nonzero(right)?))?;
+    },
+
+    0xF420 => AddStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<u32>()?;
+        let left = cpu.pop::<u32>()?;
+        cpu.push(left.wrapping_add(right))?;
+    },
+    0xF421 => SubtractStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<u32>()?;
+        let left = cpu.pop::<u32>()?;
+        cpu.push(left.wrapping_sub(right))?;
+    },
+    0xF422 => MultiplyStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<u32>()?;
+        let left = cpu.pop::<u32>()?;
+        cpu.push(left.wrapping_mul(right))?;
+    },
+    0xF423 => DivideUnsignedStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<u32>()?;
+        let left = cpu.pop::<u32>()?;
+        cpu.push(left.wrapping_div(nonzero(right)?))?;
+    },
+    0xF424 => DivideSignedStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<i32>()?;
+        let left = cpu.pop::<i32>()?;
+        cpu.push(left.wrapping_div(nonzero(right)?))?;
+    },
+    0xF425 => ModuloUnsignedStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<u32>()?;
+        let left = cpu.pop::<u32>()?;
+        cpu.push(left.wrapping_rem(nonzero(right)?))?;
+    },
+    0xF426 => ModuloSignedStackInteger32() |_mem, cpu| {
+        let right = cpu.pop::<i32>()?;
+        let left = cpu.pop::<i32>()?;
+        cpu.push(left.wrapping_rem(nonzero(right)?))?;
+    },
+
+    0xF430 => AddStackFloat32() |_mem, cpu| {
+        let right = cpu.pop::<f32>()?;
+        let left = cpu.pop::<f32>()?;
+        cpu.push(left + right)?;
+    },
+    0xF431 => SubtractStackFloat32() |_mem, cpu| {
+        let right = cpu.pop::<f32>()?;
+        let left = cpu.pop::<f32>()?;
+        cpu.push(left - right)?;
+    },
+    0xF432 => MultiplyStackFloat32() |_mem, cpu| {
+        let right = cpu.pop::<f32>()?;
+        let left = cpu.pop::<f32>()?;
+        cpu.push(left * right)?;
+    },
+    0xF433 => DivideStackFloat32() |_mem, cpu| {
+        let right = cpu.pop::<f32>()?;
+        let left = cpu.pop::<f32>()?;
+        cpu.push(left / right)?;
+    },
+    0xF434 => ModuloStackFloat32() |_mem, cpu| {
+        let right = cpu.pop::<f32>()?;
+        let left = cpu.pop::<f32>()?;
+        cpu.push(left % right)?;
+    },
+    0xF435 => PowerStackFloat32() |_mem, cpu| {
+        let right = cpu.pop::<f32>()?;
+        let left = cpu.pop::<f32>()?;
+        cpu.push(left.powf(right))?;
+    },
+
+    0xF440 => AddStackFloat64() |_mem, cpu| {
+        let right = cpu.pop::<f64>()?;
+        let left = cpu.pop::<f64>()?;
+        cpu.push(left + right)?;
+    },
+    0xF441 => SubtractStackFloat64() |_mem, cpu| {
+        let right = cpu.pop::<f64>()?;
+        let left = cpu.pop::<f64>()?;
+        cpu.push(left - right)?;
+    },
+    0xF442 => MultiplyStackFloat64() |_mem, cpu| {
+        let right = cpu.pop::<f64>()?;
+        let left = cpu.pop::<f64>()?;
+        cpu.push(left * right)?;
+    },
+    0xF443 => DivideStackFloat64() |_mem, cpu| {
+        let right = cpu.pop::<f64>()?;
+        let left = cpu.pop::<f64>()?;
+        cpu.push(left / right)?;
+    },
+    0xF444 => ModuloStackFloat64() |_mem, cpu| {
+        let right = cpu.pop::<f64>()?;
+        let left = cpu.pop::<f64>()?;
+        cpu.push(left % right)?;
+    },
+    0xF445 => PowerStackFloat64() |_mem, cpu| {
+        let right = cpu.pop::<f64>()?;
+        let left = cpu.pop::<f64>()?;
+        cpu.push(left.powf(right))?;
+    },
+
+    // Register instructions. `Load` reads the register's value from memory and
+    // returns to the end of the instruction, while `Store` writes it out like
+    // any other output.
+    0xF500 => SetRegister(register: Register, value: u64) |_mem, cpu| {
+        *cpu.register(register)? = value;
+    },
+    0xF501 => LoadRegister(register: Register, from: Offset) |mem, cpu| {
+        mem.seek(from)?;
+        *cpu.register(register)? = mem.read::<u64>()?;
+        seek_by(mem, -(from.0 as isize + 8))?;
+    },
+    0xF502 => StoreRegister(register: Register, to: OffsetPair) |mem, cpu| {
+        let value = *cpu.register(register)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0xF503 => CopyRegister(to: Register, from: Register) |_mem, cpu| {
+        *cpu.register(to)? = *cpu.register(from)?;
+    },
+    // Like `JumpIf`, but the condition is whether the register is non-zero,
+    // so a loop can branch without writing its condition into itself.
+    0xF504 => JumpIfRegister(register: Register, offset: Offset) |mem, cpu| {
+        if *cpu.register(register)? != 0 {
+            mem.seek(offset)?;
+        }
+    },
+    // Integer register arithmetic wraps around on overflow.
+    0xF510 => AddRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = left.wrapping_add(right);
+    },
+    0xF511 => SubtractRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = left.wrapping_sub(right);
+    },
+    0xF512 => MultiplyRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = left.wrapping_mul(right);
+    },
+    0xF513 => DivideUnsignedRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = left.wrapping_div(nonzero(right)?);
+    },
+    0xF514 => DivideSignedRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = (left as i64).wrapping_div(nonzero(right as i64)?) as u64;
+    },
+    0xF515 => ModuloUnsignedRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = left.wrapping_rem(nonzero(right)?);
+    },
+    0xF516 => ModuloSignedRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.register(left)?;
+        let right = *cpu.register(right)?;
+        *cpu.register(output)? = (left as i64).wrapping_rem(nonzero(right as i64)?) as u64;
+    },
+
+    0xF520 => SetFloatRegister(register: Register, value: f64) |_mem, cpu| {
+        *cpu.float_register(register)? = value;
+    },
+    0xF521 => LoadFloatRegister(register: Register, from: Offset) |mem, cpu| {
+        mem.seek(from)?;
+        *cpu.float_register(register)? = mem.read::<f64>()?;
+        seek_by(mem, -(from.0 as isize + 8))?;
+    },
+    0xF522 => StoreFloatRegister(register: Register, to: OffsetPair) |mem, cpu| {
+        let value = *cpu.float_register(register)?;
+        mem.seek(to.0)?;
+        mem.write(value)?;
+        mem.seek(to.1)?;
+    },
+    0xF523 => CopyFloatRegister(to: Register, from: Register) |_mem, cpu| {
+        *cpu.float_register(to)? = *cpu.float_register(from)?;
+    },
+    0xF530 => AddFloatRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.float_register(left)?;
+        let right = *cpu.float_register(right)?;
+        *cpu.float_register(output)? = left + right;
+    },
+    0xF531 => SubtractFloatRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.float_register(left)?;
+        let right = *cpu.float_register(right)?;
+        *cpu.float_register(output)? = left - right;
+    },
+    0xF532 => MultiplyFloatRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.float_register(left)?;
+        let right = *cpu.float_register(right)?;
+        *cpu.float_register(output)? = left * right;
+    },
+    0xF533 => DivideFloatRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.float_register(left)?;
+        let right = *cpu.float_register(right)?;
+        *cpu.float_register(output)? = left / right;
+    },
+    0xF534 => ModuloFloatRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.float_register(left)?;
+        let right = *cpu.float_register(right)?;
+        *cpu.float_register(output)? = left % right;
+    },
+    0xF535 => PowerFloatRegister(output: Register, left: Register, right: Register) |_mem, cpu| {
+        let left = *cpu.float_register(left)?;
+        let right = *cpu.float_register(right)?;
+        *cpu.float_register(output)? = left.powf(right);
+    },
+
+    // The timer fires every `interval` instructions while interrupts are
+    // enabled, jumping to the interrupt vector (see `Machine::run`).
+    0xF600 => SetInterruptVector(handler: Offset) |mem, cpu| {
+        cpu.interrupt_vector = Some(mem.position()?.wrapping_add_signed(handler.0 as isize));
+    },
+    0xF601 => EnableInterrupts() |_mem, cpu| {
+        cpu.interrupts_enabled = true;
+    },
+    0xF602 => DisableInterrupts() |_mem, cpu| {
+        cpu.interrupts_enabled = false;
+    },
+    0xF603 => ReturnFromInterrupt() |mem, cpu| {
+        let address = cpu.interrupt_return.take().ok_or(Fault::NotHandlingInterrupt)?;
+        cpu.interrupts_enabled = true;
+        mem.set_position(address)?;
+    },
+    0xF604 => SetTimer(interval: u64) |_mem, cpu| {
+        cpu.timer_interval = interval;
+    },
+    0xF605 => StoreInstructionCount(to: OffsetPair) |mem, cpu| {
+        let count = cpu.instruction_count;
+        mem.seek(to.0)?;
+        mem.write(count)?;
+        mem.seek(to.1)?;
+    },
+
+    // Thread instructions only record what they want, and the `Scheduler`
+    // carries it out after the instruction. Ids are stored in registers.
+    0xF700 => Spawn(entry: Offset, id: Register) |mem, cpu| {
+        let entry = mem.position()?.wrapping_add_signed(entry.0 as isize);
+        // Checked here so that a bad register faults in the spawning thread
+        cpu.register(id)?;
+        cpu.thread_request = Some(ThreadRequest::Spawn { entry, id });
+    },
+    0xF701 => Yield() |_mem, cpu| {
+        cpu.thread_request = Some(ThreadRequest::Yield);
+    },
+    0xF702 => Join(id: Register) |_mem, cpu| {
+        let thread = *cpu.register(id)? as usize;
+        cpu.thread_request = Some(ThreadRequest::Join { thread });
+    },
+    0xF703 => ThreadId(id: Register) |_mem, cpu| {
+        *cpu.register(id)? = cpu.thread_id as u64;
+    },
+
+    // Atomic instructions update the value at `target` in a single step, and
+    // write the value it had before to `result`.
+    0xF800 => CompareAndSwap32(target: Offset, expected: u32, new: u32, result: OffsetPair) |mem| {
+        let old = atomic_at::<_, u32>(mem, target, |value| (value == expected).then_some(new))?;
+        mem.seek(result.0)?;
+        mem.write(old)?;
+        mem.seek(result.1)?;
+    },
+    0xF801 => CompareAndSwap64(target: Offset, expected: u64, new: u64, result: OffsetPair) |mem| {
+        let old = atomic_at::<_, u64>(mem, target, |value| (value == expected).then_some(new))?;
+        mem.seek(result.0)?;
+        mem.write(old)?;
+        mem.seek(result.1)?;
+    },
+    0xF802 => FetchAdd32(target: Offset, value: u32, result: OffsetPair) |mem| {
+        let old = atomic_at::<_, u32>(mem, target, |old: u32| Some(old.wrapping_add(value)))?;
+        mem.seek(result.0)?;
+        mem.write(old)?;
+        mem.seek(result.1)?;
+    },
+    0xF803 => FetchAdd64(target: Offset, value: u64, result: OffsetPair) |mem| {
+        let old = atomic_at::<_, u64>(mem, target, |old: u64| Some(old.wrapping_add(value)))?;
+        mem.seek(result.0)?;
+        mem.write(old)?;
+        mem.seek(result.1)?;
+    },
+    0xF804 => Exchange32(target: Offset, value: u32, result: OffsetPair) |mem| {
+        let old = atomic_at::<_, u32>(mem, target, |_| Some(value))?;
+        mem.seek(result.0)?;
+        mem.write(old)?;
+        mem.seek(result.1)?;
+    },
+    0xF805 => Exchange64(target: Offset, value: u64, result: OffsetPair) |mem| {
+        let old = atomic_at::<_, u64>(mem, target, |_| Some(value))?;
+        mem.seek(result.0)?;
+        mem.write(old)?;
+        mem.seek(result.1)?;
+    },
+
+    // Channel instructions pass messages between machines through the
+    // channels connected to their ports. A received message is written as
+    // its length, as a `u16`, followed by its bytes. As that length isn't
+    // known in advance, `to.1` is relative to the start of what was written
+    // rather than its end.
+    0xF900 => Send(port: u8, len: u16, from: Offset) |mem, cpu| {
+        let channel = cpu.sender(port)?;
+        mem.seek(from)?;
+        let mut message = Vec::with_capacity(len as usize);
+        while message.len() < len as usize {
+            message.push(mem.read::<u8>()?);
+        }
+        seek_by(mem, -(from.0 as isize + len as isize))?;
+        channel.send(message);
+    },
+    0xF901 => Recv(port: u8, to: OffsetPair) |mem, cpu| {
+        let Some(message) = cpu.receiver(port)?.try_recv() else {
+            // Go back to the start of the instruction, to try again once a
+            // message has been sent
+            cpu.receiving = Some(port);
+            let len = Instruction::Recv.encoded_len() + Instruction::Recv.operand_len();
+            seek_by(mem, -(len as isize))?;
+            return Ok(());
+        };
+        cpu.receiving = None;
+        mem.seek(to.0)?;
+        write_message(mem, Some(message))?;
+        mem.seek(to.1)?;
+    },
+    // Doesn't wait, and writes whether a message was received before the
+    // message itself
+    0xF902 => TryRecv(port: u8, to: OffsetPair) |mem, cpu| {
+        let message = cpu.receiver(port)?.try_recv();
+        mem.seek(to.0)?;
+        mem.write(message.is_some())?;
+        write_message(mem, message)?;
+        seek_by(mem, -1)?;
+        mem.seek(to.1)?;
+    },
+
+    // Unknown opcodes decode to this, so it can't be used for anything else.
+    0xFFFF => InvalidOpcode() |_mem| {
+        return Err(Fault::InvalidOpcode.into());
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::{Cpu, HostFunctions, TrapFrame};
+    use crate::memory::InMemoryMemory;
 
     use super::*;
 
     #[test]
-    fn test_executing_jump() {
-        let mut mem = InMemoryMemory::from_vec(vec![0x03, 0x02]);
-        let instruction = Instruction::Jump;
-        instruction.execute(&mut mem).unwrap();
-        assert_eq!(mem.pc, 0x0205);
+    fn test_executing_jump() {
+        let mut mem = InMemoryMemory::from_vec(vec![0x03, 0x02]);
+        let instruction = Instruction::Jump;
+        instruction.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 0x0205);
+    }
+
+    #[test]
+    fn test_executing_jump_if_true() {
+        let mut mem = InMemoryMemory::from_vec(vec![0x01, 0x03, 0x02]);
+        let instruction = Instruction::JumpIf;
+        instruction.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 0x0206);
+    }
+
+    #[test]
+    fn test_executing_jump_if_false() {
+        let mut mem = InMemoryMemory::from_vec(vec![0x00, 0x03, 0x02]);
+        let instruction = Instruction::JumpIf;
+        instruction.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 0x03);
+    }
+
+    #[test]
+    fn test_executing_u32_add_on_zeroes() {
+        let mut mem = InMemoryMemory::from_vec(vec![0x00; 12]);
+        let instruction = Instruction::AddInteger32;
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 16);
+        mem.seek(Offset(-4)).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0);
+        assert_eq!(mem.memory.len(), 16);
+    }
+
+    #[test]
+    fn test_executing_u32_add_on_nonzero_values() {
+        let instruction = Instruction::AddInteger32;
+        let mut mem = InMemoryMemory::builder()
+            .data(5_u32)
+            .data(15_u32)
+            .data((Offset(0), Offset(0)))
+            .build();
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 16);
+        mem.seek(Offset(-4)).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 20);
+        assert_eq!(mem.memory.len(), 16);
+    }
+
+    #[test]
+    fn test_signed_values_round_trip() {
+        let mut buffer = [0_u8; 8];
+        (-2_i8).into_bytes(&mut buffer[0..1]);
+        assert_eq!(buffer[0], 0xFE);
+        assert_eq!(i8::from_bytes(&buffer), -2);
+
+        (-2_i16).into_bytes(&mut buffer[0..2]);
+        assert_eq!(buffer[0..2], [0xFE, 0xFF]);
+        assert_eq!(i16::from_bytes(&buffer), -2);
+    }
+
+    #[test]
+    fn test_executing_signed_divide_on_negative_values() {
+        let instruction = Instruction::DivideSignedInteger32;
+        let mut mem = InMemoryMemory::builder()
+            .data(-15_i32)
+            .data(4_i32)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.read::<i32>().unwrap(), -3);
+    }
+
+    #[test]
+    fn test_executing_wrapping_signed_add_wraps_on_overflow() {
+        let instruction = Instruction::WrappingAddSignedInteger32;
+        let mut mem = InMemoryMemory::builder()
+            .data(i32::MAX)
+            .data(2_i32)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.read::<i32>().unwrap(), i32::MIN + 1);
+    }
+
+    #[test]
+    fn test_executing_checked_signed_multiply() {
+        let instruction = Instruction::CheckedMultiplySignedInteger64;
+        let mut mem = InMemoryMemory::builder()
+            .data(-3_i64)
+            .data(7_i64)
+            .data((Offset(0), Offset(-8)))
+            .build();
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.read::<i64>().unwrap(), -21);
+    }
+
+    #[test]
+    fn test_executing_checked_signed_add_traps_on_overflow() {
+        let instruction = Instruction::CheckedAddSignedInteger64;
+        let mut mem = InMemoryMemory::builder()
+            .data(i64::MAX)
+            .data(1_i64)
+            .data((Offset(0), Offset(0)))
+            .build();
+        let result = instruction.execute(&mut mem);
+
+        assert!(matches!(
+            result,
+            Err(Trap::Fault {
+                fault: Fault::Overflow,
+                ..
+            })
+        ));
+        assert_eq!(mem.memory.len(), 20);
+    }
+
+    #[test]
+    fn test_executing_negate_and_absolute_on_minimum_value() {
+        let mut mem = InMemoryMemory::builder()
+            .data(i32::MIN)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::WrappingAbsoluteSignedInteger32
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<i32>().unwrap(), i32::MIN);
+
+        mem.pc = 0;
+        let result = Instruction::CheckedNegateSignedInteger32.execute(&mut mem);
+        assert!(matches!(
+            result,
+            Err(Trap::Fault {
+                fault: Fault::Overflow,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_executing_u8_add_wraps_on_overflow() {
+        let instruction = Instruction::AddInteger8;
+        let mut mem = InMemoryMemory::builder()
+            .data(250_u8)
+            .data(10_u8)
+            .data((Offset(0), Offset(-1)))
+            .build();
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 6);
+        assert_eq!(mem.read::<u8>().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_executing_u64_and_u32_arithmetic_wraps_on_overflow() {
+        let mut mem = InMemoryMemory::builder()
+            .data(0_u64)
+            .data(1_u64)
+            .data((Offset(0), Offset(-8)))
+            .build();
+        Instruction::SubtractInteger64.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u64>().unwrap(), u64::MAX);
+
+        let mut mem = InMemoryMemory::builder()
+            .data(0x8000_0000_u32)
+            .data(2_u32)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::MultiplyInteger32.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_executing_u16_arithmetic() {
+        let mut mem = InMemoryMemory::builder()
+            .data(1000_u16)
+            .data(7_u16)
+            .data((Offset(0), Offset(-2)))
+            .build();
+        Instruction::ModuloUnsignedInteger16
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u16>().unwrap(), 6);
+
+        mem.pc = 0;
+        Instruction::SubtractInteger16.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u16>().unwrap(), 993);
+    }
+
+    #[test]
+    fn test_executing_i8_signed_divide() {
+        let instruction = Instruction::DivideSignedInteger8;
+        let mut mem = InMemoryMemory::builder()
+            .data(-100_i8)
+            .data(3_i8)
+            .data((Offset(0), Offset(-1)))
+            .build();
+        instruction.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.read::<i8>().unwrap(), -33);
+    }
+
+    #[test]
+    fn test_extended_opcodes_round_trip() {
+        let instruction = Instruction::ByteSwapInteger32;
+        let mut bytes = [0_u8; 2];
+        assert_eq!(instruction.encoded_len(), 2);
+        instruction.encode(&mut bytes);
+        assert_eq!(bytes[0], EXTENDED_PREFIX);
+
+        let mut mem = InMemoryMemory::from_vec(bytes.to_vec());
+        assert_eq!(Instruction::decode(&mut mem).unwrap(), Some(instruction));
+        assert_eq!(Instruction::decode(&mut mem).unwrap(), None);
+    }
+
+    #[test]
+    fn test_executing_bitwise_instructions() {
+        let mut mem = InMemoryMemory::builder()
+            .data(0b1100_u8)
+            .data(0b1010_u8)
+            .data((Offset(0), Offset(-1)))
+            .build();
+        Instruction::XorInteger8.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), 0b0110);
+
+        mem.pc = 0;
+        Instruction::AndInteger8.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), 0b1000);
+    }
+
+    #[test]
+    fn test_executing_shifts() {
+        let mut mem = InMemoryMemory::builder()
+            .data(0x8000_0001_u32)
+            .data(4_u8)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::ShiftRightLogicalInteger32
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0x0800_0000);
+
+        mem.pc = 0;
+        Instruction::ShiftRightArithmeticInteger32
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0xF800_0000);
+
+        mem.pc = 0;
+        Instruction::RotateRightInteger32.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0x1800_0000);
     }
 
     #[test]
-    fn test_executing_jump_if_true() {
-        let mut mem = InMemoryMemory::from_vec(vec![0x01, 0x03, 0x02]);
-        let instruction = Instruction::JumpIf;
-        instruction.execute(&mut mem).unwrap();
-        assert_eq!(mem.pc, 0x0206);
+    fn test_shifting_by_whole_width() {
+        let mut mem = InMemoryMemory::builder()
+            .data(0x8001_u16)
+            .data(16_u8)
+            .data((Offset(0), Offset(-2)))
+            .build();
+        Instruction::ShiftLeftInteger16.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u16>().unwrap(), 0);
+
+        mem.pc = 0;
+        Instruction::ShiftRightArithmeticInteger16
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u16>().unwrap(), 0xFFFF);
     }
 
     #[test]
-    fn test_executing_jump_if_false() {
-        let mut mem = InMemoryMemory::from_vec(vec![0x00, 0x03, 0x02]);
-        let instruction = Instruction::JumpIf;
-        instruction.execute(&mut mem).unwrap();
-        assert_eq!(mem.pc, 0x03);
+    fn test_executing_bit_counts_and_byte_swap() {
+        let mut mem = InMemoryMemory::builder()
+            .data(0x0000_0000_0000_FF00_u64)
+            .data((Offset(0), Offset(-1)))
+            .build();
+        Instruction::CountOnesInteger64.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), 8);
+
+        mem.pc = 0;
+        Instruction::TrailingZerosInteger64
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), 8);
+
+        mem.pc = 0;
+        Instruction::LeadingZerosInteger64
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u8>().unwrap(), 48);
+
+        let mut mem = InMemoryMemory::builder()
+            .data(0x1122_3344_u32)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::ByteSwapInteger32.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0x4433_2211);
     }
 
     #[test]
-    fn test_executing_u32_add_on_zeroes() {
-        let mut mem = InMemoryMemory::from_vec(vec![0x00; 12]);
-        let instruction = Instruction::AddInteger32;
-        instruction.execute(&mut mem).unwrap();
+    fn test_executing_integer_comparisons() {
+        let mut mem = InMemoryMemory::builder()
+            .data(-1_i32)
+            .data(1_i32)
+            .data((Offset(0), Offset(-1)))
+            .build();
+        Instruction::LessThanSignedInteger32
+            .execute(&mut mem)
+            .unwrap();
+        assert!(mem.read::<bool>().unwrap());
+
+        mem.pc = 0;
+        Instruction::LessThanUnsignedInteger32
+            .execute(&mut mem)
+            .unwrap();
+        assert!(!mem.read::<bool>().unwrap());
+
+        mem.pc = 0;
+        Instruction::NotEqualInteger32.execute(&mut mem).unwrap();
+        assert!(mem.read::<bool>().unwrap());
+    }
 
-        assert_eq!(mem.pc, 12);
-        assert_eq!(mem.read::<u32>().unwrap(), 0);
-        assert_eq!(mem.memory.len(), 16);
+    #[test]
+    fn test_executing_float_comparisons_with_nan() {
+        let mut mem = InMemoryMemory::builder()
+            .data(f64::NAN)
+            .data(f64::NAN)
+            .data((Offset(0), Offset(-1)))
+            .build();
+        for instruction in [
+            Instruction::EqualFloat64,
+            Instruction::LessThanFloat64,
+            Instruction::LessOrEqualFloat64,
+            Instruction::GreaterThanFloat64,
+            Instruction::GreaterOrEqualFloat64,
+        ] {
+            mem.pc = 0;
+            instruction.execute(&mut mem).unwrap();
+            assert!(!mem.read::<bool>().unwrap(), "{:?}", instruction);
+        }
+
+        mem.pc = 0;
+        Instruction::NotEqualFloat64.execute(&mut mem).unwrap();
+        assert!(mem.read::<bool>().unwrap());
     }
 
     #[test]
-    fn test_executing_u32_add_on_nonzero_values() {
-        let instruction = Instruction::AddInteger32;
+    fn test_comparison_results_can_drive_jump_if() {
+        // 3 < 5, so the JumpIf skips over the two bytes of padding
         let mut mem = InMemoryMemory::builder()
-            .data(5_u32)
-            .data(15_u32)
+            .instruction(
+                Instruction::LessThanUnsignedInteger8,
+                (3_u8, 5_u8, (Offset(1), Offset(-2))),
+            )
+            .instruction(Instruction::JumpIf, (false, Offset(2)))
+            .bytes(&[0xFF, 0xFF])
+            .build();
+        while let Some(instruction) = mem.fetch().unwrap() {
+            instruction.execute(&mut mem).unwrap();
+        }
+        assert_eq!(mem.pc, 14);
+        assert_eq!(mem.memory[9], 1);
+    }
+
+    #[test]
+    fn test_executing_integer_extensions_and_truncations() {
+        let mut mem = InMemoryMemory::builder()
+            .data(-2_i32)
+            .data((Offset(0), Offset(-8)))
+            .build();
+        Instruction::SignExtendInteger32To64
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<i64>().unwrap(), -2);
+
+        mem.pc = 0;
+        Instruction::ZeroExtendInteger32To64
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u64>().unwrap(), 0xFFFF_FFFE);
+
+        let mut mem = InMemoryMemory::builder()
+            .data(0x1_2345_6789_u64)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::TruncateInteger64To32
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0x2345_6789);
+    }
+
+    #[test]
+    fn test_executing_integer_to_float_conversions() {
+        let mut mem = InMemoryMemory::builder()
+            .data(u32::MAX)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::ConvertUnsignedInteger32ToFloat32
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<f32>().unwrap(), 4294967296.0);
+
+        mem.pc = 0;
+        Instruction::ConvertSignedInteger32ToFloat32
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<f32>().unwrap(), -1.0);
+    }
+
+    #[test]
+    fn test_executing_saturating_float_to_integer_conversions() {
+        let cases = [
+            (2.5, 2, 2),
+            (3.5, 3, 4),
+            (-1.5, 0, 0),
+            (1e10, u32::MAX, u32::MAX),
+            (f64::NAN, 0, 0),
+            (f64::INFINITY, u32::MAX, u32::MAX),
+        ];
+        for (value, truncated, rounded) in cases {
+            let mut mem = InMemoryMemory::builder()
+                .data(value)
+                .data((Offset(0), Offset(-4)))
+                .build();
+            Instruction::SaturatingTruncateFloat64ToUnsignedInteger32
+                .execute(&mut mem)
+                .unwrap();
+            assert_eq!(mem.read::<u32>().unwrap(), truncated, "{}", value);
+
+            mem.pc = 0;
+            Instruction::SaturatingRoundFloat64ToUnsignedInteger32
+                .execute(&mut mem)
+                .unwrap();
+            assert_eq!(mem.read::<u32>().unwrap(), rounded, "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_executing_checked_float_to_integer_conversions() {
+        let cases = [
+            (127.9_f32, Some(127)),
+            (-128.0, Some(-128)),
+            (128.0, None),
+            (-129.0, None),
+            (f32::NAN, None),
+            (f32::NEG_INFINITY, None),
+        ];
+        for (value, expected) in cases {
+            let mut mem = InMemoryMemory::builder()
+                .data(value)
+                .data((Offset(0), Offset(-1)))
+                .build();
+            let result = Instruction::CheckedTruncateFloat32ToSignedInteger8.execute(&mut mem);
+            match expected {
+                Some(expected) => {
+                    result.unwrap();
+                    assert_eq!(mem.read::<i8>().unwrap(), expected);
+                }
+                None => assert!(matches!(
+                    result,
+                    Err(Trap::Fault {
+                        fault: Fault::InvalidConversion,
+                        ..
+                    })
+                )),
+            }
+        }
+    }
+
+    #[test]
+    fn test_checked_conversion_to_u64_rejects_two_to_the_64() {
+        let mut mem = InMemoryMemory::builder()
+            .data(18446744073709551616.0_f64)
             .data((Offset(0), Offset(0)))
             .build();
-        instruction.execute(&mut mem).unwrap();
+        assert!(Instruction::CheckedRoundFloat64ToUnsignedInteger64
+            .execute(&mut mem)
+            .is_err());
+    }
 
-        assert_eq!(mem.pc, 12);
-        assert_eq!(mem.read::<u32>().unwrap(), 20);
-        assert_eq!(mem.memory.len(), 16);
+    type UnaryFloatOp = fn(f64) -> f64;
+    type BinaryFloatOp = fn(f64, f64) -> f64;
+
+    const SPECIAL_F64S: [f64; 10] = [
+        0.0,
+        -0.0,
+        0.5,
+        -2.5,
+        std::f64::consts::PI,
+        1e300,
+        f64::MIN_POSITIVE,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ];
+
+    fn assert_same_float(actual: f64, expected: f64, context: impl std::fmt::Debug) {
+        assert!(
+            actual.to_bits() == expected.to_bits() || (actual.is_nan() && expected.is_nan()),
+            "{:?}: expected {}, got {}",
+            context,
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_executing_unary_float_maths_matches_std() {
+        let cases: [(Instruction, UnaryFloatOp); 12] = [
+            (Instruction::SquareRootFloat64, f64::sqrt),
+            (Instruction::AbsoluteFloat64, f64::abs),
+            (Instruction::FloorFloat64, f64::floor),
+            (Instruction::CeilingFloat64, f64::ceil),
+            (Instruction::RoundFloat64, f64::round),
+            (Instruction::TruncateFloat64, f64::trunc),
+            (Instruction::SineFloat64, f64::sin),
+            (Instruction::CosineFloat64, f64::cos),
+            (Instruction::TangentFloat64, f64::tan),
+            (Instruction::ExponentialFloat64, f64::exp),
+            (Instruction::NaturalLogarithmFloat64, f64::ln),
+            (Instruction::Logarithm2Float64, f64::log2),
+        ];
+        for (instruction, expected) in cases {
+            for value in SPECIAL_F64S {
+                let mut mem = InMemoryMemory::builder()
+                    .data(value)
+                    .data((Offset(0), Offset(-8)))
+                    .build();
+                instruction.execute(&mut mem).unwrap();
+                assert_same_float(
+                    mem.read::<f64>().unwrap(),
+                    expected(value),
+                    (instruction, value),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_executing_binary_float_maths_matches_std() {
+        let cases: [(Instruction, BinaryFloatOp); 3] = [
+            (Instruction::MinimumFloat64, f64::min),
+            (Instruction::MaximumFloat64, f64::max),
+            (Instruction::ArcTangent2Float64, f64::atan2),
+        ];
+        for (instruction, expected) in cases {
+            for left in SPECIAL_F64S {
+                for right in SPECIAL_F64S {
+                    let mut mem = InMemoryMemory::builder()
+                        .data(left)
+                        .data(right)
+                        .data((Offset(0), Offset(-8)))
+                        .build();
+                    instruction.execute(&mut mem).unwrap();
+                    assert_same_float(
+                        mem.read::<f64>().unwrap(),
+                        expected(left, right),
+                        (instruction, left, right),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_executing_f32_maths() {
+        let mut mem = InMemoryMemory::builder()
+            .data(2.0_f32)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::SquareRootFloat32.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<f32>().unwrap(), 2.0_f32.sqrt());
+
+        let mut mem = InMemoryMemory::builder()
+            .data(f32::NAN)
+            .data(1.0_f32)
+            .data((Offset(0), Offset(-4)))
+            .build();
+        Instruction::MaximumFloat32.execute(&mut mem).unwrap();
+        assert_eq!(mem.read::<f32>().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_executing_float_classification() {
+        for value in SPECIAL_F64S {
+            let mut mem = InMemoryMemory::builder()
+                .data(value)
+                .data((Offset(0), Offset(-1)))
+                .build();
+            Instruction::IsNanFloat64.execute(&mut mem).unwrap();
+            assert_eq!(mem.read::<bool>().unwrap(), value.is_nan());
+
+            mem.pc = 0;
+            Instruction::IsFiniteFloat64.execute(&mut mem).unwrap();
+            assert_eq!(mem.read::<bool>().unwrap(), value.is_finite());
+        }
+    }
+
+    #[test]
+    fn test_executing_jump_indirect() {
+        // The pointer lives in the operand's target, and points 0x10 bytes
+        // past itself
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(0))
+            .data(Offset(0x10))
+            .build();
+        Instruction::JumpIndirect.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 0x14);
+    }
+
+    #[test]
+    fn test_executing_load_indirect() {
+        // A two-entry table after the instruction, with a pointer to the
+        // second entry
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(0))
+            .data((Offset(10), Offset(-14)))
+            .data(Offset(4))
+            .data(0x1111_1111_u32)
+            .data(0x2222_2222_u32)
+            .data(0_u32)
+            .build();
+        Instruction::LoadIndirect4.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 6);
+        mem.seek(Offset(10)).unwrap();
+        assert_eq!(mem.read::<u32>().unwrap(), 0x2222_2222);
+    }
+
+    #[test]
+    fn test_executing_store_indirect() {
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(2))
+            .data(Offset(0))
+            .data(Offset(-6))
+            .data(0xBEEF_u16)
+            .build();
+        Instruction::StoreIndirect2.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 4);
+        assert_eq!(mem.memory[0..2], [0xEF, 0xBE]);
+    }
+
+    #[test]
+    fn test_executing_jump_indirect_absolute() {
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(0))
+            .data(Address(0x12345))
+            .build();
+        Instruction::JumpIndirectAbsolute.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 0x12345);
+    }
+
+    #[test]
+    fn test_executing_load_indirect_absolute() {
+        // The pointer holds the address of the second table entry
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(4))
+            .data((Offset(0), Offset(-4)))
+            .data(0_u32)
+            .data(Address(18))
+            .data(0x1111_1111_u32)
+            .data(0x2222_2222_u32)
+            .build();
+        Instruction::LoadIndirectAbsolute4
+            .execute(&mut mem)
+            .unwrap();
+
+        assert_eq!(mem.pc, 6);
+        assert_eq!(mem.read::<u32>().unwrap(), 0x2222_2222);
+    }
+
+    #[test]
+    fn test_executing_store_indirect_absolute() {
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(0))
+            .data(Offset(2))
+            .data(0xBEEF_u16)
+            .data(Address(10))
+            .data(0_u16)
+            .build();
+        Instruction::StoreIndirectAbsolute2
+            .execute(&mut mem)
+            .unwrap();
+
+        assert_eq!(mem.pc, 4);
+        assert_eq!(mem.memory[10..12], [0xEF, 0xBE]);
+    }
+
+    #[test]
+    fn test_executing_jump_absolute() {
+        let mut mem = InMemoryMemory::builder().data(Address(0x12345)).build();
+        Instruction::JumpAbsolute.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 0x12345);
+
+        let mut mem = InMemoryMemory::builder()
+            .data(false)
+            .data(Address(0x12345))
+            .build();
+        Instruction::JumpIfAbsolute.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 5);
+    }
+
+    #[test]
+    fn test_executing_move_absolute() {
+        let mut mem = InMemoryMemory::builder()
+            .data(Address(8))
+            .data(Address(12))
+            .data(0xDEAD_BEEF_u32)
+            .data(0_u32)
+            .build();
+        Instruction::MoveAbsolute4.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 8);
+        assert_eq!(mem.memory[12..16], [0xEF, 0xBE, 0xAD, 0xDE]);
+    }
+
+    #[test]
+    fn test_move_absolute_reaches_beyond_offsets() {
+        let mut mem = InMemoryMemory::builder()
+            .data(3_u8)
+            .data(Address(9))
+            .data(Address(0x10000))
+            .data([0x01_u8, 0x02, 0x03])
+            .build();
+        Instruction::MoveAbsoluteN.execute(&mut mem).unwrap();
+
+        assert_eq!(mem.pc, 9);
+        assert_eq!(mem.memory[0x10000..], [0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn test_push_and_pop_round_trip_through_memory() {
+        let mut cpu = Cpu::default();
+        let mut mem = InMemoryMemory::builder()
+            .data(1.5_f64.to_le_bytes())
+            .build();
+        Instruction::Push8
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.stack, 1.5_f64.to_le_bytes());
+
+        let mut mem = InMemoryMemory::builder()
+            .data((Offset(0), Offset(-8)))
+            .build();
+        Instruction::Pop8
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.read::<f64>().unwrap(), 1.5);
+        assert!(cpu.stack.is_empty());
+    }
+
+    #[test]
+    fn test_push_from_returns_to_end_of_instruction() {
+        let mut cpu = Cpu::default();
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(0))
+            .data(0xBEEF_u16)
+            .build();
+        Instruction::PushFrom2
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.pc, 2);
+        assert_eq!(cpu.pop::<u16>().unwrap(), 0xBEEF);
+    }
+
+    #[test]
+    fn test_stack_arithmetic_pops_right_operand_first() {
+        let mut cpu = Cpu::default();
+        let mut mem = InMemoryMemory::default();
+        cpu.push(10_u64).unwrap();
+        cpu.push(3_u64).unwrap();
+        Instruction::SubtractStackInteger64
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.pop::<u64>().unwrap(), 7);
+
+        cpu.push(-7_i32).unwrap();
+        cpu.push(2_i32).unwrap();
+        Instruction::DivideSignedStackInteger32
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.pop::<i32>().unwrap(), -3);
+
+        cpu.push(2.0_f32).unwrap();
+        cpu.push(10.0_f32).unwrap();
+        Instruction::PowerStackFloat32
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.pop::<f32>().unwrap(), 1024.0);
+        assert!(cpu.stack.is_empty());
+    }
+
+    #[test]
+    fn test_stack_integer_arithmetic_wraps() {
+        let mut cpu = Cpu::default();
+        let mut mem = InMemoryMemory::default();
+        cpu.push(u64::MAX).unwrap();
+        cpu.push(1_u64).unwrap();
+        Instruction::AddStackInteger64
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.pop::<u64>().unwrap(), 0);
+
+        cpu.push(0_u32).unwrap();
+        cpu.push(1_u32).unwrap();
+        Instruction::SubtractStackInteger32
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.pop::<u32>().unwrap(), u32::MAX);
+
+        cpu.push(u32::MAX).unwrap();
+        cpu.push(2_u32).unwrap();
+        Instruction::MultiplyStackInteger32
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.pop::<u32>().unwrap(), u32::MAX - 1);
+    }
+
+    #[test]
+    fn test_popping_an_empty_stack_underflows() {
+        let mut cpu = Cpu::default();
+        cpu.push(1_u32).unwrap();
+        let result = Instruction::AddStackInteger32.execute_with(
+            &mut InMemoryMemory::default(),
+            &mut cpu,
+            &mut HostFunctions::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(Trap::Fault {
+                fault: Fault::StackUnderflow,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_pushing_past_the_maximum_size_overflows() {
+        let mut cpu = Cpu {
+            max_stack_size: 4,
+            ..Cpu::default()
+        };
+        assert_eq!(cpu.push(1_u32), Ok(()));
+        assert_eq!(cpu.push(1_u8), Err(Fault::StackOverflow));
+    }
+
+    #[test]
+    fn test_register_arithmetic() {
+        let mut cpu = Cpu::default();
+        let mut mem = InMemoryMemory::builder()
+            .data(Register(1))
+            .data(Offset(0))
+            .data(-9_i64)
+            .build();
+        Instruction::LoadRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.pc, 3);
+        cpu.registers[2] = 2;
+
+        let mut mem = InMemoryMemory::builder()
+            .data((Register(0), Register(1), Register(2)))
+            .build();
+        Instruction::DivideSignedRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.registers[0] as i64, -4);
+
+        let mut mem = InMemoryMemory::builder()
+            .data(Register(0))
+            .data((Offset(0), Offset(-8)))
+            .build();
+        Instruction::StoreRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.read::<i64>().unwrap(), -4);
+    }
+
+    #[test]
+    fn test_register_arithmetic_wraps() {
+        let mut cpu = Cpu::default();
+        cpu.registers[1] = u64::MAX;
+        cpu.registers[2] = 1;
+        let mut mem = InMemoryMemory::builder()
+            .data((Register(0), Register(1), Register(2)))
+            .build();
+        Instruction::AddRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.registers[0], 0);
+
+        // Counting below zero
+        let mut mem = InMemoryMemory::builder()
+            .data((Register(0), Register(0), Register(2)))
+            .build();
+        Instruction::SubtractRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.registers[0], u64::MAX);
+    }
+
+    #[test]
+    fn test_jump_if_register() {
+        let mut cpu = Cpu::default();
+        let mut mem = InMemoryMemory::builder()
+            .data((Register(0), Offset(4)))
+            .build();
+        Instruction::JumpIfRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.pc, 3);
+
+        cpu.registers[0] = 2;
+        mem.pc = 0;
+        Instruction::JumpIfRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.pc, 7);
+    }
+
+    #[test]
+    fn test_float_register_arithmetic() {
+        let mut cpu = Cpu::default();
+        cpu.float_registers[3] = 1.5;
+        let mut mem = InMemoryMemory::builder()
+            .data(Register(4))
+            .data(2.0_f64)
+            .data((Register(5), Register(3), Register(4)))
+            .build();
+        Instruction::SetFloatRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        Instruction::MultiplyFloatRegister
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(cpu.float_registers[5], 3.0);
+    }
+
+    #[test]
+    fn test_naming_a_missing_register_faults() {
+        let mut mem = InMemoryMemory::builder()
+            .data((Register(16), Register(0)))
+            .build();
+        let result = Instruction::CopyRegister.execute(&mut mem);
+        assert!(matches!(
+            result,
+            Err(Trap::Fault {
+                fault: Fault::InvalidRegister,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_dividing_by_zero_faults() {
+        let mut mem = InMemoryMemory::builder()
+            .data(1_u16)
+            .data(0_u16)
+            .data((Offset(0), Offset(0)))
+            .build();
+        let result = Instruction::ModuloUnsignedInteger16.execute(&mut mem);
+        assert!(matches!(
+            result,
+            Err(Trap::Fault {
+                fault: Fault::DivideByZero,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_dividing_minimum_by_minus_one_wraps() {
+        let mut mem = InMemoryMemory::builder()
+            .data(i64::MIN)
+            .data(-1_i64)
+            .data((Offset(0), Offset(-8)))
+            .build();
+        Instruction::DivideSignedInteger64
+            .execute(&mut mem)
+            .unwrap();
+        assert_eq!(mem.read::<i64>().unwrap(), i64::MIN);
+    }
+
+    #[test]
+    fn test_compare_and_swap_only_writes_on_match() {
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(4))
+            .data(7_u32)
+            .data(9_u32)
+            .data((Offset(0), Offset(-4)))
+            .data(0_u32)
+            .data(7_u32)
+            .build();
+        Instruction::CompareAndSwap32.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 14);
+        assert_eq!(mem.memory[14..22], [7, 0, 0, 0, 9, 0, 0, 0]);
+
+        mem.pc = 0;
+        Instruction::CompareAndSwap32.execute(&mut mem).unwrap();
+        assert_eq!(mem.memory[14..22], [9, 0, 0, 0, 9, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_fetch_add_and_exchange_return_the_old_value() {
+        let mut mem = InMemoryMemory::builder()
+            .data(Offset(8))
+            .data(u64::MAX)
+            .data((Offset(0), Offset(-8)))
+            .data(0_u64)
+            .data(5_u64)
+            .build();
+        Instruction::FetchAdd64.execute(&mut mem).unwrap();
+        assert_eq!(mem.pc, 14);
+        mem.seek(Offset(0)).unwrap();
+        assert_eq!(mem.read::<u64>().unwrap(), 5);
+        assert_eq!(mem.read::<u64>().unwrap(), 4);
+
+        mem.pc = 0;
+        Instruction::Exchange64.execute(&mut mem).unwrap();
+        mem.seek(Offset(0)).unwrap();
+        assert_eq!(mem.read::<u64>().unwrap(), 4);
+        assert_eq!(mem.read::<u64>().unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn test_storing_a_trap_address_that_doesnt_fit_faults() {
+        let mut cpu = Cpu {
+            trap_frame: Some(TrapFrame {
+                code: 0,
+                address: 1 << 32,
+                resume: 0,
+            }),
+            ..Cpu::default()
+        };
+        let mut mem = InMemoryMemory::builder()
+            .data((Offset(0), Offset(0)))
+            .data(0_u32)
+            .build();
+        assert!(matches!(
+            Instruction::StoreTrapAddress.execute_with(
+                &mut mem,
+                &mut cpu,
+                &mut HostFunctions::default()
+            ),
+            Err(Trap::Fault {
+                fault: Fault::AddressOutOfRange {
+                    address: 0x1_0000_0000
+                },
+                ..
+            })
+        ));
+        assert_eq!(mem.memory[4..], [0; 4]);
+    }
+
+    #[test]
+    fn test_try_recv_reports_whether_there_was_a_message() {
+        let channel = crate::network::Channel::new();
+        let mut cpu = Cpu::default();
+        cpu.ports
+            .insert(3, crate::network::Port::Receiver(channel.clone()));
+        let mut mem = InMemoryMemory::builder()
+            .data(3_u8)
+            .data((Offset(0), Offset(0)))
+            .data([0xFF_u8; 4])
+            .build();
+        Instruction::TryRecv
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.memory[5..], [0x00, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(mem.pc, 5);
+
+        channel.send(vec![0x42]);
+        mem.pc = 0;
+        Instruction::TryRecv
+            .execute_with(&mut mem, &mut cpu, &mut HostFunctions::default())
+            .unwrap();
+        assert_eq!(mem.memory[5..], [0x01, 0x01, 0x00, 0x42]);
+        assert_eq!(mem.pc, 5);
+        assert_eq!(cpu.receiving, None);
+    }
+
+    #[test]
+    fn test_operand_len_covers_all_operands() {
+        assert_eq!(Instruction::Noop.operand_len(), 0);
+        assert_eq!(Instruction::AddInteger64.operand_len(), 20);
+        assert_eq!(Instruction::MoveAbsoluteN.operand_len(), 9);
     }
 }
@@ -1,7 +1,8 @@
 use esolang::{
     machine::Machine,
-    memory::{FileMemory, InMemoryMemory},
-    types::{Instruction, Offset},
+    memory::{FileMemory, InMemoryMemory, Memory},
+    trap::Fault,
+    types::{Instruction, Offset, OffsetPair},
 };
 
 #[test]
@@ -10,7 +11,7 @@ fn can_create_and_run_basic_programs() {
         InMemoryMemory::builder()
             .instruction(
                 Instruction::AddFloat32,
-                (1.0_f32, 2.0_f32, (Offset(5), Offset(-7))),
+                (1.0_f32, 2.0_f32, (Offset(5), Offset(-9))),
             )
             .instruction(
                 Instruction::PowerFloat32,
@@ -21,5 +22,60 @@ fn can_create_and_run_basic_programs() {
     );
     let mut machine = Machine::with_memory(memory);
     machine.run().unwrap();
-    // TODO: assertions
+
+    // The sum is written into the right operand of the power instruction
+    machine.memory.seek(Offset(-4)).unwrap();
+    assert_eq!(machine.memory.read::<f32>().unwrap(), 27.0);
+}
+
+#[test]
+fn journaled_programs_write_back_to_the_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("image");
+    let program = InMemoryMemory::builder()
+        .instruction(Instruction::MoveN, (3_u8, Offset(0), Offset(0)))
+        .bytes(&[0x11, 0x22, 0x33])
+        .bytes(&[0x00; 3])
+        .build();
+    std::fs::write(&path, &program.memory).unwrap();
+
+    let memory = FileMemory::with_journal(&path).unwrap();
+    let mut machine = Machine::with_memory(memory);
+    machine.run().unwrap();
+
+    assert_eq!(
+        std::fs::read(&path).unwrap()[6..],
+        [0x11, 0x22, 0x33, 0x11, 0x22, 0x33]
+    );
+}
+
+#[test]
+fn faulting_instructions_leave_the_image_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("image");
+    // The trap handler is the end of memory, so the machine just stops
+    let program = InMemoryMemory::builder()
+        .instruction(
+            Instruction::SetTrapHandler,
+            (Fault::Overflow.code(), Offset(10)),
+        )
+        .instruction(
+            Instruction::HostCall,
+            (1_u16, Offset(0), OffsetPair(Offset(0), Offset(0))),
+        )
+        .data(0_u8)
+        .build();
+    std::fs::write(&path, &program.memory).unwrap();
+
+    // Writes its argument, then faults
+    let memory = FileMemory::with_journal(&path).unwrap();
+    let mut machine = Machine::with_memory(memory).host_call(1, |mem, args| {
+        mem.set_position(args)?;
+        mem.write(0xFF_u8)?;
+        Err(Fault::Overflow.into())
+    });
+    machine.run().unwrap();
+
+    assert_eq!(machine.cpu.trap_frame.unwrap().address, 4);
+    assert_eq!(std::fs::read(&path).unwrap(), program.memory);
 }
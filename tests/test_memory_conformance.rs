@@ -0,0 +1,113 @@
+use esolang::{
+    machine::Machine,
+    memory::{
+        CachedMemory, Cursor, FileMemory, InMemoryMemory, Memory, OverlayMemory, PagedMemory,
+    },
+    types::{Instruction, Offset},
+};
+
+// Every backend should behave identically from the point of view of a
+// program, so each of these tests is run against all of them.
+macro_rules! conformance_tests {
+    ($($backend:ident => $from_bytes:expr,)+) => {
+        $(mod $backend {
+            use super::*;
+
+            fn memory(bytes: &[u8]) -> impl Memory<Error: std::fmt::Debug> {
+                #[allow(clippy::redundant_closure_call)]
+                ($from_bytes)(bytes)
+            }
+
+            #[test]
+            fn reads_advance_the_cursor() {
+                let mut mem = memory(&[0x01, 0x02, 0x03, 0x04, 0x05]);
+                assert_eq!(mem.read::<u8>().unwrap(), 0x01);
+                assert_eq!(mem.read::<u32>().unwrap(), 0x05040302);
+            }
+
+            #[test]
+            fn read_if_present_returns_none_at_end() {
+                let mut mem = memory(&[0x01, 0x02, 0x03]);
+                assert_eq!(mem.read_if_present::<[u8; 2]>().unwrap(), Some([0x01, 0x02]));
+                assert_eq!(mem.read_if_present::<[u8; 2]>().unwrap(), None);
+                assert_eq!(mem.read_if_present::<u8>().unwrap(), Some(0x03));
+                assert_eq!(mem.read_if_present::<u8>().unwrap(), None);
+            }
+
+            #[test]
+            fn writes_advance_the_cursor() {
+                let mut mem = memory(&[0x00; 4]);
+                mem.write(0x01_u8).unwrap();
+                mem.write(0x02_u8).unwrap();
+                mem.seek(Offset(-2)).unwrap();
+                assert_eq!(mem.read::<[u8; 4]>().unwrap(), [0x01, 0x02, 0x00, 0x00]);
+            }
+
+            #[test]
+            fn seeking_moves_in_both_directions() {
+                let mut mem = memory(&[0x01, 0x02, 0x03, 0x04]);
+                mem.seek(Offset(3)).unwrap();
+                assert_eq!(mem.read::<u8>().unwrap(), 0x04);
+                mem.seek(Offset(-3)).unwrap();
+                assert_eq!(mem.read::<u8>().unwrap(), 0x02);
+            }
+
+            #[test]
+            fn setting_the_position_is_absolute() {
+                let mut mem = memory(&[0x01, 0x02, 0x03, 0x04]);
+                mem.seek(Offset(1)).unwrap();
+                mem.set_position(3).unwrap();
+                assert_eq!(mem.position().unwrap(), 3);
+                assert_eq!(mem.read::<u8>().unwrap(), 0x04);
+                assert_eq!(mem.position().unwrap(), 4);
+            }
+
+            #[test]
+            fn writing_past_the_end_extends_memory() {
+                let mut mem = memory(&[0x01]);
+                mem.seek(Offset(3)).unwrap();
+                mem.write(0xFF_u8).unwrap();
+                mem.seek(Offset(-4)).unwrap();
+                assert_eq!(mem.read::<[u8; 4]>().unwrap(), [0x01, 0x00, 0x00, 0xFF]);
+                assert_eq!(mem.read_if_present::<u8>().unwrap(), None);
+            }
+
+            #[test]
+            fn seeking_beyond_small_offsets_is_possible() {
+                let mut mem = memory(&[]);
+                mem.seek(Offset(i16::MAX)).unwrap();
+                mem.seek(Offset(i16::MAX)).unwrap();
+                mem.write(0xFF_u8).unwrap();
+                mem.seek(Offset(-1)).unwrap();
+                assert_eq!(mem.read::<u8>().unwrap(), 0xFF);
+            }
+
+            #[test]
+            fn runs_programs() {
+                let program = InMemoryMemory::builder()
+                    .instruction(Instruction::AddInteger32, (5_u32, 15_u32, (Offset(0), Offset(0))))
+                    .data(0_u32)
+                    .build();
+                let mut machine = Machine::with_memory(memory(&program.memory));
+                machine.run().unwrap();
+
+                machine.memory.seek(Offset(-4)).unwrap();
+                assert_eq!(machine.memory.read::<u32>().unwrap(), 20);
+            }
+        })+
+    };
+}
+
+conformance_tests! {
+    in_memory_memory => |bytes: &[u8]| InMemoryMemory::from_vec(bytes.to_vec()),
+    file_memory => |bytes: &[u8]| {
+        FileMemory::with_file(InMemoryMemory::builder().bytes(bytes).to_tmp_file().unwrap())
+    },
+    paged_memory => |bytes: &[u8]| PagedMemory::from_vec(bytes.to_vec()),
+    overlay_memory => |bytes: &[u8]| {
+        OverlayMemory::with_file(InMemoryMemory::builder().bytes(bytes).to_tmp_file().unwrap())
+            .unwrap()
+    },
+    cursor => |bytes: &[u8]| Cursor::new(bytes.to_vec()),
+    cached_memory => |bytes: &[u8]| CachedMemory::with_memory(InMemoryMemory::from_vec(bytes.to_vec())),
+}